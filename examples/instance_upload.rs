@@ -0,0 +1,75 @@
+// Rough comparison of the instance upload paths at 50k sprites on a headless
+// renderer. Needs a GPU; run with `cargo run --release --example instance_upload`.
+use std::time::{Duration, Instant};
+
+use engine2d::assets::{Atlas, Texture, TextureOptions};
+use engine2d::renderer::{InstanceRaw, InstanceUpload, Renderer, build_batches};
+use engine2d::scene::{BlendMode, MaterialId, Sprite, Transform};
+use glam::Vec2;
+
+const INSTANCES: usize = 50_000;
+const WARMUP_FRAMES: u32 = 20;
+const FRAMES: u32 = 200;
+
+fn main() {
+    env_logger::init();
+    let mut renderer = match pollster::block_on(Renderer::new_headless(640, 480)) {
+        Ok(renderer) => renderer,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let texture = Texture::from_rgba8(
+        renderer.device(),
+        renderer.queue(),
+        1,
+        1,
+        &[255; 4],
+        "Bench Texture",
+        TextureOptions::default(),
+    )
+    .expect("1x1 texture");
+    renderer.set_textures([&texture]);
+
+    let atlas = Atlas::new(1, 1, 1);
+    let sprite = Sprite::new(Vec2::splat(2.0), 0, [1.0; 4]);
+    let mut instances: Vec<InstanceRaw> = (0..INSTANCES)
+        .map(|index| {
+            let transform = Transform {
+                position: Vec2::new((index % 300) as f32, (index / 300) as f32) * 2.0,
+                ..Transform::default()
+            };
+            InstanceRaw::from_components(&transform, &sprite, &atlas)
+        })
+        .collect();
+    let batches = build_batches((0..INSTANCES).map(|_| (0, BlendMode::Alpha, MaterialId::DEFAULT)));
+
+    for upload in [InstanceUpload::WriteBuffer, InstanceUpload::Staging] {
+        renderer.set_instance_upload(upload);
+        let mut frame = |instances: &mut Vec<InstanceRaw>| {
+            // Changes the data every frame, as a moving scene would.
+            instances.rotate_left(1);
+            let upload_start = Instant::now();
+            renderer.update_instances(instances, &batches);
+            let upload_time = upload_start.elapsed();
+            renderer.render().expect("headless render");
+            upload_time
+        };
+        for _ in 0..WARMUP_FRAMES {
+            frame(&mut instances);
+        }
+        let start = Instant::now();
+        let upload_time: Duration = (0..FRAMES).map(|_| frame(&mut instances)).sum();
+        renderer.device().poll(wgpu::Maintain::Wait);
+        let total = start.elapsed();
+        println!(
+            "{:?}: {:.3} ms upload, {:.3} ms frame (mean of {} frames, {} instances)",
+            upload,
+            upload_time.as_secs_f64() * 1000.0 / FRAMES as f64,
+            total.as_secs_f64() * 1000.0 / FRAMES as f64,
+            FRAMES,
+            INSTANCES,
+        );
+    }
+}
@@ -1,14 +1,31 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, Cursor, Seek};
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 use std::time::SystemTime;
 
 use serde::Deserialize;
 use wgpu::util::DeviceExt;
 
+use crate::scene::{Animation, AnimationDesc};
+
 const ATLAS_CONFIG_PATH: &str = "assets/atlas.json";
+const SPRITE_SHADER_PATH: &str = "assets/sprite.wgsl";
 const DEFAULT_TEXTURE_PATH: &str = "assets/sprites.png";
+const SUPPORTED_FORMATS: [image::ImageFormat; 4] = [
+    image::ImageFormat::Png,
+    image::ImageFormat::Jpeg,
+    image::ImageFormat::Bmp,
+    image::ImageFormat::WebP,
+];
 const DEFAULT_ATLAS_COLUMNS: u32 = 2;
 const DEFAULT_ATLAS_ROWS: u32 = 2;
 const DEFAULT_ATLAS_TILE_SIZE: u32 = 32;
+const MAX_ATLAS_GRID_PIXELS: u64 = 16384;
+
+const EMBEDDED_ATLAS_CONFIG: &str = include_str!("../assets/atlas.json");
+const EMBEDDED_TEXTURE: &[u8] = include_bytes!("../assets/embedded/sprites.png");
 
 const ATLAS_COLORS: [[u8; 4]; 6] = [
     [235, 70, 70, 255],
@@ -23,43 +40,300 @@ const ATLAS_COLORS: [[u8; 4]; 6] = [
 #[serde(default)]
 struct AtlasConfig {
     texture: Option<String>,
-    columns: u32,
-    rows: u32,
-    tile_size: u32,
+    columns: i64,
+    rows: i64,
+    tile_size: TileSize,
+    tile_width: Option<i64>,
+    tile_height: Option<i64>,
+    frames: Vec<FrameRect>,
+    names: HashMap<String, u32>,
+    clips: HashMap<String, AnimationDesc>,
+    mipmaps: bool,
+    filter: TextureFilter,
+    wrap: TextureWrap,
+    max_texture_size: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+enum TileSize {
+    Square(i64),
+    Rect([i64; 2]),
+}
+
+impl AtlasConfig {
+    fn columns(&self) -> u32 {
+        checked_grid_size("columns", self.columns, DEFAULT_ATLAS_COLUMNS)
+    }
+
+    fn rows(&self) -> u32 {
+        checked_grid_size("rows", self.rows, DEFAULT_ATLAS_ROWS)
+    }
+
+    fn tile_dimensions(&self) -> (u32, u32) {
+        let (width, height) = match self.tile_size {
+            TileSize::Square(size) => (size, size),
+            TileSize::Rect([width, height]) => (width, height),
+        };
+        (
+            checked_grid_size(
+                "tile width",
+                self.tile_width.unwrap_or(width),
+                DEFAULT_ATLAS_TILE_SIZE,
+            ),
+            checked_grid_size(
+                "tile height",
+                self.tile_height.unwrap_or(height),
+                DEFAULT_ATLAS_TILE_SIZE,
+            ),
+        )
+    }
+
+    fn texture_options(&self) -> TextureOptions {
+        TextureOptions {
+            mipmaps: self.mipmaps,
+            filter: self.filter,
+            wrap: self.wrap,
+            max_dimension: self.max_texture_size,
+        }
+    }
+}
+
+// A tile's source rectangle in texture pixels, for packed atlases.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct FrameRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
 }
 
 impl Default for AtlasConfig {
     fn default() -> Self {
         Self {
             texture: None,
-            columns: DEFAULT_ATLAS_COLUMNS,
-            rows: DEFAULT_ATLAS_ROWS,
-            tile_size: DEFAULT_ATLAS_TILE_SIZE,
+            columns: DEFAULT_ATLAS_COLUMNS.into(),
+            rows: DEFAULT_ATLAS_ROWS.into(),
+            tile_size: TileSize::Square(DEFAULT_ATLAS_TILE_SIZE.into()),
+            tile_width: None,
+            tile_height: None,
+            frames: Vec::new(),
+            names: HashMap::new(),
+            clips: HashMap::new(),
+            mipmaps: false,
+            filter: TextureFilter::Nearest,
+            wrap: TextureWrap::ClampToEdge,
+            max_texture_size: None,
+        }
+    }
+}
+
+// How a texture is uploaded and sampled; the default suits pixel art.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextureOptions {
+    // Builds a mip chain on the CPU to reduce shimmer when zoomed out.
+    pub mipmaps: bool,
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+    // Loaded images larger than this on either side are downscaled to fit.
+    pub max_dimension: Option<u32>,
+}
+
+impl TextureOptions {
+    fn max_dimension(&self, device: &wgpu::Device) -> u32 {
+        let limit = device.limits().max_texture_dimension_2d;
+        self.max_dimension.map_or(limit, |max| max.clamp(1, limit))
+    }
+}
+
+// Nearest keeps pixel art crisp, Linear suits smoothly scaled sprites.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureFilter {
+    #[default]
+    Nearest,
+    Linear,
+}
+
+impl TextureFilter {
+    fn to_wgpu(self) -> wgpu::FilterMode {
+        match self {
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+// What sampling outside 0..1 returns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureWrap {
+    #[default]
+    ClampToEdge,
+    Repeat,
+}
+
+impl TextureWrap {
+    fn to_wgpu(self) -> wgpu::AddressMode {
+        match self {
+            TextureWrap::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            TextureWrap::Repeat => wgpu::AddressMode::Repeat,
         }
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Debug)]
 pub struct Atlas {
     pub columns: u32,
     pub rows: u32,
-    pub tile_size: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    // When non-empty, tiles come from these rectangles instead of the grid.
+    pub frames: Vec<FrameRect>,
+    // Tile indices by name, so sprites need not hard-code numbers.
+    pub names: HashMap<String, u32>,
+    // Named animations over this atlas's tiles; see `Sprite::play_clip`.
+    pub clips: HashMap<String, AnimationDesc>,
+    texture_width: u32,
+    texture_height: u32,
 }
 
 impl Atlas {
-    fn from_config(config: &AtlasConfig) -> Self {
+    // A grid of square tiles.
+    pub fn new(columns: u32, rows: u32, tile_size: u32) -> Self {
+        Self::with_tile_size(columns, rows, tile_size, tile_size)
+    }
+
+    pub fn with_tile_size(columns: u32, rows: u32, tile_width: u32, tile_height: u32) -> Self {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        let tile_width = tile_width.max(1);
+        let tile_height = tile_height.max(1);
         Self {
-            columns: config.columns.max(1),
-            rows: config.rows.max(1),
-            tile_size: config.tile_size.max(1),
+            columns,
+            rows,
+            tile_width,
+            tile_height,
+            frames: Vec::new(),
+            names: HashMap::new(),
+            clips: HashMap::new(),
+            texture_width: columns * tile_width,
+            texture_height: rows * tile_height,
+        }
+    }
+
+    pub fn with_frames(mut self, frames: Vec<FrameRect>) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    pub fn with_names(mut self, names: HashMap<String, u32>) -> Self {
+        self.names = names;
+        self
+    }
+
+    pub fn with_clips(mut self, clips: HashMap<String, AnimationDesc>) -> Self {
+        self.clips = clips;
+        self
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<u32> {
+        self.names.get(name).copied()
+    }
+
+    // A fresh animation for the named clip, tagged with the name.
+    pub fn clip(&self, name: &str) -> Option<Animation> {
+        self.clips
+            .get(name)
+            .map(|clip| clip.to_animation().with_clip(name))
+    }
+
+    fn from_config(config: &AtlasConfig) -> Self {
+        let (tile_width, tile_height) = config.tile_dimensions();
+        let (columns, rows) = (config.columns(), config.rows());
+        let grid_width = columns as u64 * tile_width as u64;
+        let grid_height = rows as u64 * tile_height as u64;
+        if config.frames.is_empty()
+            && (grid_width > MAX_ATLAS_GRID_PIXELS || grid_height > MAX_ATLAS_GRID_PIXELS)
+        {
+            log::warn!(
+                "Atlas grid of {}x{} tiles at {}x{} pixels is {}x{} pixels, more than the \
+                 {} most GPUs allow; check columns, rows and tile_size",
+                columns,
+                rows,
+                tile_width,
+                tile_height,
+                grid_width,
+                grid_height,
+                MAX_ATLAS_GRID_PIXELS
+            );
+        }
+        let atlas = Self::with_tile_size(columns, rows, tile_width, tile_height)
+            .with_frames(config.frames.clone())
+            .with_names(config.names.clone())
+            .with_clips(config.clips.clone());
+        for (name, index) in &atlas.names {
+            if *index >= atlas.tile_count() {
+                log::warn!(
+                    "Tile name {:?} points at {} but the atlas has {} tiles",
+                    name,
+                    index,
+                    atlas.tile_count()
+                );
+            }
+        }
+        for (name, clip) in &atlas.clips {
+            if clip.frames.is_empty() || clip.fps <= 0.0 {
+                log::warn!(
+                    "Animation clip {:?} has no frames or a non-positive fps",
+                    name
+                );
+            } else if let Some(frame) = clip.frames.iter().find(|&&f| f >= atlas.tile_count()) {
+                log::warn!(
+                    "Animation clip {:?} uses tile {} but the atlas has {} tiles",
+                    name,
+                    frame,
+                    atlas.tile_count()
+                );
+            }
         }
+        atlas
+    }
+
+    // Frame rectangles are normalized against the bound texture's size.
+    pub fn set_texture_size(&mut self, width: u32, height: u32) {
+        self.texture_width = width.max(1);
+        self.texture_height = height.max(1);
     }
 
     pub fn tile_count(&self) -> u32 {
-        self.columns * self.rows
+        if self.frames.is_empty() {
+            self.columns * self.rows
+        } else {
+            self.frames.len() as u32
+        }
+    }
+
+    // Size of the tile in texels.
+    pub fn tile_pixel_size(&self, index: u32) -> glam::Vec2 {
+        if !self.frames.is_empty() {
+            let frame = self.frames[index as usize % self.frames.len()];
+            return glam::Vec2::new(frame.w as f32, frame.h as f32);
+        }
+        glam::Vec2::new(self.tile_width as f32, self.tile_height as f32)
     }
 
     pub fn uv_for_index(&self, index: u32) -> (glam::Vec2, glam::Vec2) {
+        if !self.frames.is_empty() {
+            let frame = self.frames[index as usize % self.frames.len()];
+            let texture_size =
+                glam::Vec2::new(self.texture_width as f32, self.texture_height as f32);
+            let min = glam::Vec2::new(frame.x as f32, frame.y as f32) / texture_size;
+            let max = glam::Vec2::new((frame.x + frame.w) as f32, (frame.y + frame.h) as f32)
+                / texture_size;
+            return (min, max);
+        }
+
         let count = self.tile_count().max(1);
         let idx = index % count;
         let tile_x = idx % self.columns;
@@ -72,130 +346,719 @@ impl Atlas {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum TextureError {
+    Empty,
+    // The file could not be opened.
+    Load(PathBuf),
+    // The file opened but is not a supported image, or its data is corrupt.
+    Decode {
+        path: PathBuf,
+        reason: String,
+    },
+    ZeroSize,
+    // Raw uploads past the size limit; loaded images are downscaled instead.
+    TooLarge {
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+    // A layer's byte length is not width * height * 4.
+    DataLength {
+        layer: usize,
+        expected: usize,
+        found: usize,
+    },
+    // Every layer must match the first one's dimensions.
+    SizeMismatch {
+        path: PathBuf,
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+    TooManyLayers {
+        count: u32,
+        max: u32,
+    },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::Empty => write!(f, "texture needs at least one layer"),
+            TextureError::Load(path) => write!(f, "failed to load {}", path.display()),
+            TextureError::Decode { path, reason } => {
+                write!(f, "failed to decode {}: {}", path.display(), reason)
+            }
+            TextureError::ZeroSize => write!(f, "texture has a zero width or height"),
+            TextureError::TooLarge { width, height, max } => {
+                write!(
+                    f,
+                    "{}x{} exceeds the maximum dimension of {}",
+                    width, height, max
+                )
+            }
+            TextureError::DataLength {
+                layer,
+                expected,
+                found,
+            } => write!(
+                f,
+                "layer {} holds {} bytes but {} were expected",
+                layer, found, expected
+            ),
+            TextureError::SizeMismatch {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} is {}x{} but the array layers are {}x{}",
+                path.display(),
+                found.0,
+                found.1,
+                expected.0,
+                expected.1
+            ),
+            TextureError::TooManyLayers { count, max } => {
+                write!(f, "{} layers exceeds the device limit of {}", count, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+// Always viewed as a 2D array; a plain texture is a single layer.
 pub struct Texture {
     _texture: wgpu::Texture,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub mip_level_count: u32,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
 }
 
 impl Texture {
-    fn from_rgba8(
+    pub fn from_rgba8(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         width: u32,
         height: u32,
         data: &[u8],
         label: &str,
-    ) -> Self {
+        options: TextureOptions,
+    ) -> Result<Self, TextureError> {
+        Self::from_rgba8_layers(device, queue, width, height, &[data], label, options)
+    }
+
+    // Each entry of `layers` is one tightly packed RGBA8 image of the given size.
+    pub fn from_rgba8_layers(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        layers: &[&[u8]],
+        label: &str,
+        options: TextureOptions,
+    ) -> Result<Self, TextureError> {
+        validate_rgba8(width, height, layers, options.max_dimension(device))?;
+        let max = device.limits().max_texture_array_layers;
+        let count = layers.len() as u32;
+        if count > max {
+            return Err(TextureError::TooManyLayers { count, max });
+        }
+
+        let mut data = Vec::new();
+        let mut mip_level_count = 1;
+        for layer in layers {
+            if options.mipmaps {
+                let (chain, count) = build_mip_chain(width, height, layer);
+                data.extend_from_slice(&chain);
+                mip_level_count = count;
+            } else {
+                data.extend_from_slice(layer);
+            }
+        }
         let size = wgpu::Extent3d {
             width,
             height,
-            depth_or_array_layers: 1,
+            depth_or_array_layers: layers.len().max(1) as u32,
         };
         let texture = device.create_texture_with_data(
             queue,
             &wgpu::TextureDescriptor {
                 label: Some(label),
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format: wgpu::TextureFormat::Rgba8UnormSrgb,
                 usage: wgpu::TextureUsages::TEXTURE_BINDING,
                 view_formats: &[],
             },
-            wgpu::util::TextureDataOrder::MipMajor,
-            data,
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &data,
         );
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Sprite Sampler"),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_u: options.wrap.to_wgpu(),
+            address_mode_v: options.wrap.to_wgpu(),
             address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+            mag_filter: options.filter.to_wgpu(),
+            min_filter: options.filter.to_wgpu(),
+            mipmap_filter: if mip_level_count > 1 {
+                wgpu::FilterMode::Linear
+            } else {
+                wgpu::FilterMode::Nearest
+            },
             ..Default::default()
         });
 
-        Self {
+        Ok(Self {
             _texture: texture,
+            width,
+            height,
+            layers: size.depth_or_array_layers,
+            mip_level_count,
             view,
             sampler,
+        })
+    }
+
+    pub fn from_path(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        options: TextureOptions,
+    ) -> Result<Self, TextureError> {
+        let image = open_image(path)?;
+        Self::from_image(device, queue, &image, options)
+    }
+
+    // Loads same-sized images as the layers of one texture, in order.
+    pub fn array_from_paths(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paths: &[PathBuf],
+        options: TextureOptions,
+    ) -> Result<Self, TextureError> {
+        let decoded = DecodedTexture::decode(paths)?;
+        Self::from_decoded(device, queue, decoded, options)
+    }
+
+    // The GPU half of a load; decoding may have happened on another thread.
+    pub fn from_decoded(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        decoded: DecodedTexture,
+        options: TextureOptions,
+    ) -> Result<Self, TextureError> {
+        let decoded = decoded.fit_within(options.max_dimension(device))?;
+        let layers: Vec<&[u8]> = decoded.layers.iter().map(Vec::as_slice).collect();
+        let label = if layers.len() > 1 {
+            "Sprite Texture Array"
+        } else {
+            "Sprite Texture"
+        };
+        Self::from_rgba8_layers(
+            device,
+            queue,
+            decoded.width,
+            decoded.height,
+            &layers,
+            label,
+            options,
+        )
+    }
+
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        options: TextureOptions,
+    ) -> Option<Self> {
+        let reader = image::ImageReader::new(Cursor::new(bytes));
+        let image = match decode_image(reader, "texture bytes") {
+            Ok(image) => image,
+            Err(reason) => {
+                log::warn!("Failed to decode texture bytes: {}", reason);
+                return None;
+            }
+        };
+        match Self::from_image(device, queue, &image, options) {
+            Ok(texture) => Some(texture),
+            Err(err) => {
+                log::warn!("Rejected texture bytes: {}", err);
+                None
+            }
         }
     }
 
-    fn from_path(device: &wgpu::Device, queue: &wgpu::Queue, path: &Path) -> Option<Self> {
-        let image = image::open(path).ok()?;
-        let rgba = image.to_rgba8();
+    fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &image::DynamicImage,
+        options: TextureOptions,
+    ) -> Result<Self, TextureError> {
+        let rgba = fit_within(image.to_rgba8(), options.max_dimension(device));
         let width = rgba.width();
         let height = rgba.height();
         let data = rgba.into_raw();
-        Some(Self::from_rgba8(
+        Self::from_rgba8(
             device,
             queue,
             width,
             height,
             &data,
             "Sprite Texture",
-        ))
+            options,
+        )
     }
 }
 
-pub struct Assets {
+// CPU-side RGBA8 pixels for every layer of a texture, all the same size.
+pub struct DecodedTexture {
+    pub width: u32,
+    pub height: u32,
+    pub layers: Vec<Vec<u8>>,
+}
+
+impl DecodedTexture {
+    // Reads and converts each file without touching the GPU.
+    pub fn decode(paths: &[PathBuf]) -> Result<Self, TextureError> {
+        let mut decoded: Option<Self> = None;
+        for path in paths {
+            let image = open_image(path)?.to_rgba8();
+            let found = image.dimensions();
+            match decoded.as_mut() {
+                Some(decoded) if (decoded.width, decoded.height) != found => {
+                    return Err(TextureError::SizeMismatch {
+                        path: path.clone(),
+                        expected: (decoded.width, decoded.height),
+                        found,
+                    });
+                }
+                Some(decoded) => decoded.layers.push(image.into_raw()),
+                None => {
+                    decoded = Some(Self {
+                        width: found.0,
+                        height: found.1,
+                        layers: vec![image.into_raw()],
+                    })
+                }
+            }
+        }
+        decoded.ok_or(TextureError::Empty)
+    }
+
+    fn fit_within(self, max: u32) -> Result<Self, TextureError> {
+        if self.width <= max && self.height <= max {
+            return Ok(self);
+        }
+        let expected = self.width as usize * self.height as usize * 4;
+        let (mut width, mut height) = (self.width, self.height);
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for (layer, data) in self.layers.into_iter().enumerate() {
+            let found = data.len();
+            let image = image::RgbaImage::from_raw(self.width, self.height, data).ok_or(
+                TextureError::DataLength {
+                    layer,
+                    expected,
+                    found,
+                },
+            )?;
+            let image = fit_within(image, max);
+            (width, height) = image.dimensions();
+            layers.push(image.into_raw());
+        }
+        Ok(Self {
+            width,
+            height,
+            layers,
+        })
+    }
+}
+
+// A `DecodedTexture::decode` running on a background thread.
+pub struct PendingDecode {
+    paths: Vec<PathBuf>,
+    receiver: Receiver<Result<DecodedTexture, TextureError>>,
+}
+
+impl PendingDecode {
+    pub fn spawn(paths: Vec<PathBuf>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let thread_paths = paths.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(DecodedTexture::decode(&thread_paths));
+        });
+        Self { paths, receiver }
+    }
+
+    // None while the decode is still running.
+    pub fn poll(&self) -> Option<Result<DecodedTexture, TextureError>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(TextureError::Load(
+                self.paths.first().cloned().unwrap_or_default(),
+            ))),
+        }
+    }
+}
+
+pub struct AtlasPage {
     pub atlas: Atlas,
     pub texture: Texture,
+    options: TextureOptions,
+    texture_paths: Vec<PathBuf>,
+    texture_mtimes: Vec<Option<SystemTime>>,
+    pending: Option<PendingDecode>,
+}
+
+impl AtlasPage {
+    fn load(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        atlas: Atlas,
+        texture_path: PathBuf,
+        options: TextureOptions,
+    ) -> Self {
+        let texture_mtimes = vec![file_mtime(&texture_path)];
+        let texture = load_texture_or_procedural(device, queue, &atlas, &texture_path, options);
+        let mut page = Self {
+            atlas,
+            texture,
+            options,
+            texture_paths: vec![texture_path],
+            texture_mtimes,
+            pending: None,
+        };
+        page.sync_texture_size();
+        page
+    }
+
+    fn load_array(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        atlas: Atlas,
+        texture_paths: Vec<PathBuf>,
+        options: TextureOptions,
+    ) -> Result<Self, TextureError> {
+        let texture = Texture::array_from_paths(device, queue, &texture_paths, options)?;
+        let mut page = Self {
+            atlas,
+            texture,
+            options,
+            texture_mtimes: texture_paths.iter().map(|path| file_mtime(path)).collect(),
+            texture_paths,
+            pending: None,
+        };
+        page.sync_texture_size();
+        Ok(page)
+    }
+
+    fn is_array(&self) -> bool {
+        self.texture_paths.len() > 1
+    }
+
+    fn current_mtimes(&self) -> Vec<Option<SystemTime>> {
+        self.texture_paths
+            .iter()
+            .map(|path| file_mtime(path))
+            .collect()
+    }
+
+    fn set_atlas(&mut self, atlas: Atlas) {
+        self.atlas = atlas;
+        self.sync_texture_size();
+    }
+
+    fn start_reload(&mut self) {
+        self.texture_mtimes = self.current_mtimes();
+        self.pending = Some(PendingDecode::spawn(self.texture_paths.clone()));
+    }
+
+    fn finish_reload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
+        let Some(result) = self.pending.as_ref().and_then(PendingDecode::poll) else {
+            return false;
+        };
+        self.pending = None;
+        let texture =
+            result.and_then(|decoded| Texture::from_decoded(device, queue, decoded, self.options));
+        match texture {
+            Ok(texture) => {
+                log::info!("Reloaded texture from {}", self.texture_paths[0].display());
+                self.texture = texture;
+            }
+            Err(err) if self.is_array() => {
+                log::warn!("Keeping previous texture array: {}", err);
+                return false;
+            }
+            Err(err) => {
+                log::warn!("Failed to reload texture: {}", err);
+                self.texture = fallback_texture(
+                    device,
+                    queue,
+                    &self.atlas,
+                    &self.texture_paths[0],
+                    self.options,
+                );
+            }
+        }
+        self.sync_texture_size();
+        true
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    fn sync_texture_size(&mut self) {
+        self.atlas
+            .set_texture_size(self.texture.width, self.texture.height);
+    }
+}
+
+// Atlas pages: the config file's entries first, then pages added with `add_page`.
+pub struct Assets {
+    pages: Vec<AtlasPage>,
+    config_pages: usize,
     config_path: PathBuf,
-    texture_path: PathBuf,
     config_mtime: Option<SystemTime>,
-    texture_mtime: Option<SystemTime>,
+    config_version: u32,
+    shader_path: PathBuf,
+    shader_mtime: Option<SystemTime>,
 }
 
 impl Assets {
     pub fn load(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
         let config_path = PathBuf::from(ATLAS_CONFIG_PATH);
-        let (config, config_mtime) = load_atlas_config(&config_path);
-        let atlas = Atlas::from_config(&config);
-        let texture_path = texture_path_from_config(&config);
-        let texture_mtime = file_mtime(&texture_path);
-        let texture = load_texture_or_procedural(device, queue, &atlas, &texture_path);
+        let (configs, config_mtime) = load_atlas_config(&config_path);
+        let shader_path = PathBuf::from(SPRITE_SHADER_PATH);
+        let shader_mtime = file_mtime(&shader_path);
+        let pages: Vec<AtlasPage> = configs
+            .iter()
+            .map(|config| load_config_page(device, queue, config))
+            .collect();
         Self {
-            atlas,
-            texture,
+            config_pages: pages.len(),
+            pages,
             config_path,
-            texture_path,
             config_mtime,
-            texture_mtime,
+            config_version: 0,
+            shader_path,
+            shader_mtime,
+        }
+    }
+
+    pub fn add_page(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_path: impl Into<PathBuf>,
+        atlas: Atlas,
+        options: TextureOptions,
+    ) -> u32 {
+        self.pages.push(AtlasPage::load(
+            device,
+            queue,
+            atlas,
+            texture_path.into(),
+            options,
+        ));
+        (self.pages.len() - 1) as u32
+    }
+
+    // Adds one page whose texture is an array with a layer per path.
+    pub fn load_array(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paths: &[PathBuf],
+        atlas: Atlas,
+        options: TextureOptions,
+    ) -> Result<u32, TextureError> {
+        let page = AtlasPage::load_array(device, queue, atlas, paths.to_vec(), options)?;
+        self.pages.push(page);
+        Ok((self.pages.len() - 1) as u32)
+    }
+
+    pub fn pages(&self) -> &[AtlasPage] {
+        &self.pages
+    }
+
+    // Unknown ids fall back to page 0 so a bad `texture_id` still draws.
+    pub fn page(&self, texture_id: u32) -> &AtlasPage {
+        self.pages
+            .get(texture_id as usize)
+            .unwrap_or(&self.pages[0])
+    }
+
+    pub fn atlas(&self, texture_id: u32) -> &Atlas {
+        &self.page(texture_id).atlas
+    }
+
+    pub fn textures(&self) -> impl Iterator<Item = &Texture> {
+        self.pages.iter().map(|page| &page.texture)
+    }
+
+    // The atlas config, the sprite shader and every page texture.
+    pub fn watched_paths(&self) -> Vec<PathBuf> {
+        [self.config_path.clone(), self.shader_path.clone()]
+            .into_iter()
+            .chain(
+                self.pages
+                    .iter()
+                    .flat_map(|page| page.texture_paths.iter().cloned()),
+            )
+            .collect()
+    }
+
+    // Re-reads the config and every texture without comparing mtimes.
+    pub fn reload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
+        let (configs, mtime) = load_atlas_config(&self.config_path);
+        self.config_mtime = mtime;
+        let changed = self.apply_config(device, queue, &configs, true);
+        for page in &mut self.pages[self.config_pages..] {
+            page.start_reload();
         }
+        changed
     }
 
+    // Starts background reloads for changed files and swaps in any that have finished.
     pub fn reload_if_changed(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
-        let mut reload_texture = false;
+        let mut reloaded = false;
         let current_config_mtime = file_mtime(&self.config_path);
         if current_config_mtime != self.config_mtime {
-            let (config, mtime) = load_atlas_config(&self.config_path);
-            self.atlas = Atlas::from_config(&config);
-            let new_texture_path = texture_path_from_config(&config);
-            if new_texture_path != self.texture_path {
-                self.texture_path = new_texture_path;
-                reload_texture = true;
-            }
+            let (configs, mtime) = load_atlas_config(&self.config_path);
+            reloaded = self.apply_config(device, queue, &configs, false);
             self.config_mtime = mtime;
         }
 
-        let current_texture_mtime = file_mtime(&self.texture_path);
-        if current_texture_mtime != self.texture_mtime {
-            reload_texture = true;
+        for page in &mut self.pages {
+            if page.current_mtimes() != page.texture_mtimes {
+                page.start_reload();
+            }
+        }
+
+        self.poll_loads(device, queue) || reloaded
+    }
+
+    // Uploads background decodes that have finished since the last call.
+    pub fn poll_loads(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
+        let mut reloaded = false;
+        for page in &mut self.pages {
+            reloaded |= page.finish_reload(device, queue);
         }
+        reloaded
+    }
 
-        if reload_texture {
-            self.texture = load_texture_or_procedural(device, queue, &self.atlas, &self.texture_path);
-            self.texture_mtime = file_mtime(&self.texture_path);
+    // The WGSL in assets/sprite.wgsl, or None for the built-in shader.
+    pub fn sprite_shader(&self) -> Option<String> {
+        if !self.shader_path.exists() {
+            return None;
         }
+        match std::fs::read_to_string(&self.shader_path) {
+            Ok(source) => Some(source),
+            Err(err) => {
+                log::warn!("Failed to read {}: {}", self.shader_path.display(), err);
+                None
+            }
+        }
+    }
+
+    // Whether the shader file changed since the last call.
+    pub fn sprite_shader_changed(&mut self) -> bool {
+        let mtime = file_mtime(&self.shader_path);
+        let changed = mtime != self.shader_mtime;
+        self.shader_mtime = mtime;
+        changed
+    }
 
-        reload_texture
+    pub fn config_version(&self) -> u32 {
+        self.config_version
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.pages.iter().any(AtlasPage::is_loading)
+    }
+
+    fn apply_config(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        configs: &[AtlasConfig],
+        force: bool,
+    ) -> bool {
+        self.config_version = self.config_version.wrapping_add(1);
+        let mut reloaded = false;
+        for (index, config) in configs.iter().enumerate() {
+            if index >= self.config_pages {
+                self.pages
+                    .insert(index, load_config_page(device, queue, config));
+                reloaded = true;
+                continue;
+            }
+            let page = &mut self.pages[index];
+            page.set_atlas(Atlas::from_config(config));
+            let new_texture_path = texture_path_from_config(config);
+            let new_options = config.texture_options();
+            let changed =
+                page.texture_paths != [new_texture_path.clone()] || new_options != page.options;
+            if force || changed {
+                page.texture_paths = vec![new_texture_path];
+                page.options = new_options;
+                page.start_reload();
+            }
+        }
+        if configs.len() < self.config_pages {
+            self.pages.drain(configs.len()..self.config_pages);
+            reloaded = true;
+        }
+        if configs.len() != self.config_pages && self.pages.len() > configs.len() {
+            log::warn!(
+                "Atlas page count changed; runtime pages now start at id {}",
+                configs.len()
+            );
+        }
+        self.config_pages = configs.len();
+        reloaded
+    }
+}
+
+fn load_config_page(device: &wgpu::Device, queue: &wgpu::Queue, config: &AtlasConfig) -> AtlasPage {
+    AtlasPage::load(
+        device,
+        queue,
+        Atlas::from_config(config),
+        texture_path_from_config(config),
+        config.texture_options(),
+    )
+}
+
+fn checked_grid_size(field: &str, value: i64, default: u32) -> u32 {
+    match u32::try_from(value) {
+        Ok(0) => {
+            log::warn!("Atlas config {} is 0, using 1", field);
+            1
+        }
+        Ok(value) => value,
+        Err(_) => {
+            log::warn!(
+                "Atlas config {} is {}, which is not a valid size; using the default {}",
+                field,
+                value,
+                default
+            );
+            default
+        }
     }
 }
 
@@ -207,36 +1070,118 @@ fn texture_path_from_config(config: &AtlasConfig) -> PathBuf {
         .unwrap_or_else(|| PathBuf::from(DEFAULT_TEXTURE_PATH))
 }
 
-fn load_atlas_config(path: &Path) -> (AtlasConfig, Option<SystemTime>) {
+fn load_atlas_config(path: &Path) -> (Vec<AtlasConfig>, Option<SystemTime>) {
     let contents = match std::fs::read_to_string(path) {
         Ok(contents) => contents,
-        Err(_) => return (AtlasConfig::default(), file_mtime(path)),
+        Err(_) => {
+            let configs = parse_atlas_config(EMBEDDED_ATLAS_CONFIG).unwrap_or_else(|err| {
+                log::warn!("Failed to parse embedded atlas config: {}", err);
+                vec![AtlasConfig::default()]
+            });
+            return (configs, file_mtime(path));
+        }
     };
 
-    match serde_json::from_str(&contents) {
-        Ok(config) => (config, file_mtime(path)),
+    match parse_atlas_config(&contents) {
+        Ok(configs) => (configs, file_mtime(path)),
         Err(err) => {
             log::warn!("Failed to parse {}: {}", path.display(), err);
-            (AtlasConfig::default(), file_mtime(path))
+            (vec![AtlasConfig::default()], file_mtime(path))
         }
     }
 }
 
+fn parse_atlas_config(json: &str) -> Result<Vec<AtlasConfig>, serde_json::Error> {
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    let configs = if value.is_array() {
+        serde_json::from_value(value)?
+    } else {
+        vec![serde_json::from_value(value)?]
+    };
+    if configs.is_empty() {
+        return Ok(vec![AtlasConfig::default()]);
+    }
+    Ok(configs)
+}
+
 fn load_texture_or_procedural(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     atlas: &Atlas,
     texture_path: &Path,
+    options: TextureOptions,
 ) -> Texture {
-    if let Some(texture) = Texture::from_path(device, queue, texture_path) {
-        log::info!("Loaded texture from {}", texture_path.display());
+    match Texture::from_path(device, queue, texture_path, options) {
+        Ok(texture) => {
+            log::info!("Loaded texture from {}", texture_path.display());
+            texture
+        }
+        Err(err) => {
+            if !matches!(err, TextureError::Load(_)) {
+                log::warn!("{}", err);
+            }
+            fallback_texture(device, queue, atlas, texture_path, options)
+        }
+    }
+}
+
+fn open_image(path: &Path) -> Result<image::DynamicImage, TextureError> {
+    let reader = image::ImageReader::open(path).map_err(|_| TextureError::Load(path.into()))?;
+    decode_image(reader, &path.display().to_string()).map_err(|reason| TextureError::Decode {
+        path: path.into(),
+        reason,
+    })
+}
+
+fn decode_image<R: BufRead + Seek>(
+    reader: image::ImageReader<R>,
+    source: &str,
+) -> Result<image::DynamicImage, String> {
+    let reader = reader
+        .with_guessed_format()
+        .map_err(|err| err.to_string())?;
+    let format = match reader.format() {
+        Some(format) if SUPPORTED_FORMATS.contains(&format) => format,
+        Some(format) => {
+            return Err(format!(
+                "{:?} images are not supported (use PNG, JPEG, BMP or WebP)",
+                format
+            ));
+        }
+        None => return Err("unrecognized image format".to_string()),
+    };
+    let image = reader.decode().map_err(|err| err.to_string())?;
+    log::info!(
+        "Decoded {} as {:?}, {}x{}",
+        source,
+        format,
+        image.width(),
+        image.height()
+    );
+    Ok(image)
+}
+
+fn fallback_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    atlas: &Atlas,
+    texture_path: &Path,
+    options: TextureOptions,
+) -> Texture {
+    if texture_path == Path::new(DEFAULT_TEXTURE_PATH)
+        && let Some(texture) = Texture::from_bytes(device, queue, EMBEDDED_TEXTURE, options)
+    {
+        log::info!(
+            "Using embedded texture in place of {}",
+            texture_path.display()
+        );
         texture
     } else {
         log::warn!(
-            "Falling back to procedural atlas texture (missing {})",
+            "Falling back to procedural atlas texture in place of {}",
             texture_path.display()
         );
-        create_procedural_atlas_texture(device, queue, atlas)
+        create_procedural_atlas_texture(device, queue, atlas, options)
     }
 }
 
@@ -244,23 +1189,140 @@ fn create_procedural_atlas_texture(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     atlas: &Atlas,
+    options: TextureOptions,
 ) -> Texture {
-    let width = atlas.columns * atlas.tile_size;
-    let height = atlas.rows * atlas.tile_size;
+    let full_width = atlas.columns as u64 * atlas.tile_width as u64;
+    let full_height = atlas.rows as u64 * atlas.tile_height as u64;
+    let (width, height) = procedural_texture_size(atlas, options.max_dimension(device));
     let mut texels = vec![0u8; (width * height * 4) as usize];
 
     for y in 0..height {
         for x in 0..width {
-            let tile_x = x / atlas.tile_size;
-            let tile_y = y / atlas.tile_size;
-            let tile_index = (tile_y * atlas.columns + tile_x) as usize;
+            let tile_x = x as u64 * full_width / width as u64 / atlas.tile_width as u64;
+            let tile_y = y as u64 * full_height / height as u64 / atlas.tile_height as u64;
+            let tile_index = (tile_y * atlas.columns as u64 + tile_x) as usize;
             let color = ATLAS_COLORS[tile_index % ATLAS_COLORS.len()];
             let idx = ((y * width + x) * 4) as usize;
             texels[idx..idx + 4].copy_from_slice(&color);
         }
     }
 
-    Texture::from_rgba8(device, queue, width, height, &texels, "Procedural Atlas")
+    Texture::from_rgba8(
+        device,
+        queue,
+        width,
+        height,
+        &texels,
+        "Procedural Atlas",
+        options,
+    )
+    .expect("procedural atlas is sized to fit")
+}
+
+fn procedural_texture_size(atlas: &Atlas, max: u32) -> (u32, u32) {
+    let full_width = atlas.columns as u64 * atlas.tile_width as u64;
+    let full_height = atlas.rows as u64 * atlas.tile_height as u64;
+    let scale = (max as f64 / full_width.max(full_height) as f64).min(1.0);
+    let width = ((full_width as f64 * scale).round() as u32).max(1);
+    let height = ((full_height as f64 * scale).round() as u32).max(1);
+    (width, height)
+}
+
+fn fit_within(image: image::RgbaImage, max: u32) -> image::RgbaImage {
+    let (width, height) = image.dimensions();
+    if width <= max && height <= max {
+        return image;
+    }
+    let scale = max as f64 / width.max(height) as f64;
+    let fitted_width = ((width as f64 * scale).round() as u32).clamp(1, max);
+    let fitted_height = ((height as f64 * scale).round() as u32).clamp(1, max);
+    log::warn!(
+        "Downscaling {}x{} texture to {}x{} to fit the maximum dimension of {}",
+        width,
+        height,
+        fitted_width,
+        fitted_height,
+        max
+    );
+    image::imageops::resize(
+        &image,
+        fitted_width,
+        fitted_height,
+        image::imageops::FilterType::Triangle,
+    )
+}
+
+// Checks raw RGBA8 layers against what wgpu will accept for a texture.
+pub fn validate_rgba8(
+    width: u32,
+    height: u32,
+    layers: &[&[u8]],
+    max_dimension: u32,
+) -> Result<(), TextureError> {
+    if width == 0 || height == 0 {
+        return Err(TextureError::ZeroSize);
+    }
+    if width > max_dimension || height > max_dimension {
+        return Err(TextureError::TooLarge {
+            width,
+            height,
+            max: max_dimension,
+        });
+    }
+    if layers.is_empty() {
+        return Err(TextureError::Empty);
+    }
+    let expected = width as usize * height as usize * 4;
+    for (layer, data) in layers.iter().enumerate() {
+        if data.len() != expected {
+            return Err(TextureError::DataLength {
+                layer,
+                expected,
+                found: data.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn build_mip_chain(width: u32, height: u32, data: &[u8]) -> (Vec<u8>, u32) {
+    let mut chain = data.to_vec();
+    let mut level_start = 0;
+    let (mut level_width, mut level_height) = (width as usize, height as usize);
+    let mut level_count = 1;
+    while level_width > 1 || level_height > 1 {
+        let next_width = (level_width / 2).max(1);
+        let next_height = (level_height / 2).max(1);
+        let mut next = vec![0u8; next_width * next_height * 4];
+        for y in 0..next_height {
+            for x in 0..next_width {
+                let xs = [
+                    (x * 2).min(level_width - 1),
+                    (x * 2 + 1).min(level_width - 1),
+                ];
+                let ys = [
+                    (y * 2).min(level_height - 1),
+                    (y * 2 + 1).min(level_height - 1),
+                ];
+                for channel in 0..4 {
+                    let mut sum = 0u32;
+                    for sy in ys {
+                        for sx in xs {
+                            let idx = level_start + (sy * level_width + sx) * 4 + channel;
+                            sum += chain[idx] as u32;
+                        }
+                    }
+                    next[(y * next_width + x) * 4 + channel] = ((sum + 2) / 4) as u8;
+                }
+            }
+        }
+        level_start = chain.len();
+        chain.extend_from_slice(&next);
+        level_width = next_width;
+        level_height = next_height;
+        level_count += 1;
+    }
+    (chain, level_count)
 }
 
 fn file_mtime(path: &Path) -> Option<SystemTime> {
@@ -268,3 +1330,299 @@ fn file_mtime(path: &Path) -> Option<SystemTime> {
         .and_then(|meta| meta.modified())
         .ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::PlaybackMode;
+    use glam::Vec2;
+
+    #[test]
+    fn odd_sized_frames_map_to_their_own_uvs() {
+        let mut atlas = Atlas::new(1, 1, 1).with_frames(vec![
+            FrameRect {
+                x: 0,
+                y: 0,
+                w: 10,
+                h: 7,
+            },
+            FrameRect {
+                x: 10,
+                y: 3,
+                w: 22,
+                h: 29,
+            },
+        ]);
+        atlas.set_texture_size(64, 32);
+        assert_eq!(atlas.tile_count(), 2);
+        assert_eq!(
+            atlas.uv_for_index(0),
+            (Vec2::ZERO, Vec2::new(10.0 / 64.0, 7.0 / 32.0))
+        );
+        assert_eq!(
+            atlas.uv_for_index(1),
+            (
+                Vec2::new(10.0 / 64.0, 3.0 / 32.0),
+                Vec2::new(32.0 / 64.0, 1.0)
+            )
+        );
+        assert_eq!(atlas.tile_pixel_size(1), Vec2::new(22.0, 29.0));
+        assert_eq!(atlas.uv_for_index(2), atlas.uv_for_index(0));
+    }
+
+    #[test]
+    fn mip_chain_of_2x2_has_two_levels() {
+        let texels = [
+            [0, 0, 0, 255],
+            [100, 0, 0, 255],
+            [0, 200, 0, 255],
+            [0, 0, 40, 255],
+        ];
+        let (chain, levels) = build_mip_chain(2, 2, texels.as_flattened());
+        assert_eq!(levels, 2);
+        assert_eq!(chain.len(), (4 + 1) * 4);
+        assert_eq!(&chain[16..], &[25, 50, 10, 255]);
+
+        let (_, levels) = build_mip_chain(4, 1, &[255; 16]);
+        assert_eq!(levels, 3);
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("engine2d-{}-{}", std::process::id(), name))
+    }
+
+    fn write_png(path: &Path, width: u32, height: u32) {
+        image::RgbaImage::from_pixel(width, height, image::Rgba([255, 0, 0, 255]))
+            .save(path)
+            .unwrap();
+    }
+
+    #[test]
+    fn array_layers_of_different_sizes_are_rejected() {
+        let (first, second) = (temp_path("layer0.png"), temp_path("layer1.png"));
+        write_png(&first, 4, 4);
+        write_png(&second, 4, 8);
+        let result = DecodedTexture::decode(&[first.clone(), second.clone()]);
+        assert_eq!(
+            result.err(),
+            Some(TextureError::SizeMismatch {
+                path: second.clone(),
+                expected: (4, 4),
+                found: (4, 8),
+            })
+        );
+        std::fs::remove_file(&first).unwrap();
+        std::fs::remove_file(&second).unwrap();
+    }
+
+    #[test]
+    fn atlas_config_reads_one_object_or_a_list() {
+        let single = parse_atlas_config(r#"{ "texture": "a.png", "columns": 4 }"#).unwrap();
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].texture.as_deref(), Some("a.png"));
+        assert_eq!((single[0].columns(), single[0].rows()), (4, 2));
+
+        let list =
+            parse_atlas_config(r#"[{ "texture": "a.png" }, { "texture": "b.png", "rows": 3 }]"#)
+                .unwrap();
+        let textures: Vec<_> = list.iter().map(|c| c.texture.as_deref()).collect();
+        assert_eq!(textures, vec![Some("a.png"), Some("b.png")]);
+        assert_eq!(list[1].rows(), 3);
+
+        let empty = parse_atlas_config("[]").unwrap();
+        assert_eq!(empty.len(), 1);
+        assert_eq!(empty[0].texture, None);
+        assert!(parse_atlas_config("{ \"columns\": ").is_err());
+    }
+
+    fn wait_for(pending: &PendingDecode) -> Result<DecodedTexture, TextureError> {
+        for _ in 0..500 {
+            if let Some(result) = pending.poll() {
+                return result;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("background decode did not finish");
+    }
+
+    #[test]
+    fn pending_decode_hands_over_pixels_or_the_error() {
+        let path = temp_path("pending.png");
+        write_png(&path, 3, 2);
+        let decoded = wait_for(&PendingDecode::spawn(vec![path.clone()])).unwrap();
+        assert_eq!((decoded.width, decoded.height), (3, 2));
+        assert_eq!(decoded.layers, vec![[255, 0, 0, 255].repeat(6)]);
+        std::fs::remove_file(&path).unwrap();
+
+        let missing = temp_path("missing.png");
+        let result = wait_for(&PendingDecode::spawn(vec![missing.clone()]));
+        assert_eq!(result.err(), Some(TextureError::Load(missing)));
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn page_keeps_its_texture_until_the_reload_is_uploaded() {
+        let renderer = crate::renderer::headless(4, 4);
+        let (device, queue) = (renderer.device(), renderer.queue());
+        let path = temp_path("page.png");
+        write_png(&path, 8, 8);
+        let mut page = AtlasPage::load(
+            device,
+            queue,
+            Atlas::new(2, 2, 4),
+            path.clone(),
+            TextureOptions::default(),
+        );
+        assert_eq!(page.texture.width, 8);
+
+        write_png(&path, 16, 8);
+        page.start_reload();
+        assert!(page.is_loading());
+        let mut swapped = false;
+        for _ in 0..500 {
+            swapped = page.finish_reload(device, queue);
+            if swapped {
+                break;
+            }
+            assert_eq!(page.texture.width, 8);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(swapped);
+        assert!(!page.is_loading());
+        assert_eq!((page.texture.width, page.texture.height), (16, 8));
+        assert_eq!(page.atlas.texture_width, 16);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rectangular_tiles_size_uvs_and_the_procedural_texture() {
+        let config = parse_atlas_config(r#"{ "columns": 4, "rows": 2, "tile_size": [16, 24] }"#)
+            .unwrap()
+            .remove(0);
+        assert_eq!(config.tile_dimensions(), (16, 24));
+        let atlas = Atlas::from_config(&config);
+        assert_eq!((atlas.tile_width, atlas.tile_height), (16, 24));
+        assert_eq!(atlas.tile_pixel_size(5), Vec2::new(16.0, 24.0));
+        assert_eq!(
+            atlas.uv_for_index(5),
+            (Vec2::new(0.25, 0.5), Vec2::new(0.5, 1.0))
+        );
+        assert_eq!(procedural_texture_size(&atlas, 4096), (64, 48));
+        assert_eq!(procedural_texture_size(&atlas, 32), (32, 24));
+
+        let tall = parse_atlas_config(r#"{ "tile_size": 16, "tile_height": 24 }"#)
+            .unwrap()
+            .remove(0);
+        assert_eq!(tall.tile_dimensions(), (16, 24));
+    }
+
+    #[test]
+    fn malformed_rgba8_uploads_are_rejected() {
+        let pixel = [255u8; 4];
+        assert_eq!(validate_rgba8(1, 1, &[&pixel], 16), Ok(()));
+        assert_eq!(
+            validate_rgba8(2, 1, &[&pixel], 16),
+            Err(TextureError::DataLength {
+                layer: 0,
+                expected: 8,
+                found: 4,
+            })
+        );
+        assert_eq!(
+            validate_rgba8(1, 1, &[&pixel, &pixel[..3]], 16),
+            Err(TextureError::DataLength {
+                layer: 1,
+                expected: 4,
+                found: 3,
+            })
+        );
+        assert_eq!(
+            validate_rgba8(0, 4, &[&[]], 16),
+            Err(TextureError::ZeroSize)
+        );
+        assert_eq!(
+            validate_rgba8(17, 1, &[&[0; 68]], 16),
+            Err(TextureError::TooLarge {
+                width: 17,
+                height: 1,
+                max: 16,
+            })
+        );
+    }
+
+    #[test]
+    fn tile_names_resolve_to_indices() {
+        let config = parse_atlas_config(r#"{ "names": { "grass": 0, "water": 3 } }"#)
+            .unwrap()
+            .remove(0);
+        let atlas = Atlas::from_config(&config);
+        assert_eq!(atlas.index_of("grass"), Some(0));
+        assert_eq!(atlas.index_of("water"), Some(3));
+        assert_eq!(atlas.index_of("lava"), None);
+        assert_eq!(
+            atlas.uv_for_index(atlas.index_of("water").unwrap()),
+            (Vec2::splat(0.5), Vec2::ONE)
+        );
+    }
+
+    #[test]
+    fn png_and_bmp_of_the_same_pixels_decode_alike() {
+        let pixels = image::RgbaImage::from_fn(3, 2, |x, y| {
+            image::Rgba([x as u8 * 80, y as u8 * 200, 40, 255])
+        });
+        let decode = |format| {
+            let mut bytes = Cursor::new(Vec::new());
+            pixels.write_to(&mut bytes, format).unwrap();
+            bytes.set_position(0);
+            decode_image(image::ImageReader::new(bytes), "test image")
+                .unwrap()
+                .to_rgba8()
+        };
+        assert_eq!(decode(image::ImageFormat::Png), pixels);
+        assert_eq!(decode(image::ImageFormat::Bmp), pixels);
+
+        let garbage = image::ImageReader::new(Cursor::new(b"not an image".to_vec()));
+        assert!(decode_image(garbage, "garbage").is_err());
+    }
+
+    #[test]
+    fn zero_or_negative_grid_sizes_are_replaced() {
+        assert_eq!(checked_grid_size("columns", 0, DEFAULT_ATLAS_COLUMNS), 1);
+        assert_eq!(checked_grid_size("columns", -3, DEFAULT_ATLAS_COLUMNS), 2);
+        assert_eq!(checked_grid_size("columns", 7, DEFAULT_ATLAS_COLUMNS), 7);
+
+        let config = parse_atlas_config(r#"{ "columns": 0, "rows": -1 }"#)
+            .unwrap()
+            .remove(0);
+        let atlas = Atlas::from_config(&config);
+        assert_eq!((atlas.columns, atlas.rows), (1, DEFAULT_ATLAS_ROWS));
+    }
+
+    #[test]
+    fn clips_from_the_config_become_tagged_animations() {
+        let config = parse_atlas_config(
+            r#"{
+                "clips": {
+                    "walk": { "frames": [0, 1, 2, 3], "fps": 8 },
+                    "die": { "frames": [2, 3], "fps": 4, "mode": "once", "pausable": false }
+                }
+            }"#,
+        )
+        .unwrap()
+        .remove(0);
+        let atlas = Atlas::from_config(&config);
+
+        let walk = atlas.clip("walk").unwrap();
+        assert_eq!(walk.frames(), &[0, 1, 2, 3]);
+        assert_eq!(walk.fps(), 8.0);
+        assert_eq!(walk.mode(), PlaybackMode::Loop);
+        assert!(walk.pausable());
+        assert_eq!(walk.clip(), Some("walk"));
+
+        let die = atlas.clip("die").unwrap();
+        assert_eq!(die.frames(), &[2, 3]);
+        assert_eq!(die.mode(), PlaybackMode::Once);
+        assert!(!die.pausable());
+        assert!(atlas.clip("run").is_none());
+    }
+}
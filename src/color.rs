@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+
+// An RGBA color in 0..1 with sRGB-encoded channels and linear alpha.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "[f32; 4]", into = "[f32; 4]")]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgba(1.0, 1.0, 1.0, 1.0);
+    pub const BLACK: Color = Color::rgba(0.0, 0.0, 0.0, 1.0);
+    pub const TRANSPARENT: Color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self::rgba(r, g, b, 1.0)
+    }
+
+    pub fn from_u8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::rgba(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            a as f32 / 255.0,
+        )
+    }
+
+    // Accepts "#rrggbb" or "#rrggbbaa"; the '#' is optional.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !matches!(digits.len(), 6 | 8) || !digits.is_ascii() {
+            return None;
+        }
+        let channel = |index: usize| u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16);
+        let alpha = if digits.len() == 8 {
+            channel(3).ok()?
+        } else {
+            255
+        };
+        Some(Self::from_u8(
+            channel(0).ok()?,
+            channel(1).ok()?,
+            channel(2).ok()?,
+            alpha,
+        ))
+    }
+
+    // Rounds to the nearest byte, clamping out-of-range channels.
+    pub fn to_u8(self) -> [u8; 4] {
+        self.to_array()
+            .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    // "#rrggbb" for opaque colors, "#rrggbbaa" otherwise.
+    pub fn to_hex(self) -> String {
+        let [r, g, b, a] = self.to_u8();
+        if a == 255 {
+            format!("#{:02x}{:02x}{:02x}", r, g, b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+        }
+    }
+
+    pub fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    // Decodes the color channels for shading; alpha is already linear.
+    pub fn to_linear(self) -> [f32; 4] {
+        [
+            srgb_to_linear(self.r),
+            srgb_to_linear(self.g),
+            srgb_to_linear(self.b),
+            self.a,
+        ]
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+impl From<[f32; 4]> for Color {
+    fn from([r, g, b, a]: [f32; 4]) -> Self {
+        Self::rgba(r, g, b, a)
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> Self {
+        color.to_array()
+    }
+}
+
+// The piecewise sRGB transfer function from IEC 61966-2-1.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_and_bytes_round_trip() {
+        let color = Color::from_hex("#ff8000").unwrap();
+        assert_eq!(color.to_u8(), [255, 128, 0, 255]);
+        assert_eq!(color.to_hex(), "#ff8000");
+        let translucent = Color::from_hex("10203040").unwrap();
+        assert_eq!(translucent.to_u8(), [0x10, 0x20, 0x30, 0x40]);
+        assert_eq!(translucent.to_hex(), "#10203040");
+        assert_eq!(
+            Color::from_u8(1, 2, 3, 4).to_u8(),
+            [1, 2, 3, 4],
+            "bytes survive the trip through f32"
+        );
+        assert_eq!(Color::rgba(1.5, -0.5, 0.5, 1.0).to_u8(), [255, 0, 128, 255]);
+        for bad in ["", "#fff", "#12345", "#gg0000", "#ff00ff0"] {
+            assert_eq!(Color::from_hex(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn srgb_conversion_matches_known_values() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+        assert!((srgb_to_linear(0.04) - 0.04 / 12.92).abs() < 1e-7);
+        for value in [0.0, 0.002, 0.1, 0.5, 0.9, 1.0] {
+            assert!((linear_to_srgb(srgb_to_linear(value)) - value).abs() < 1e-5);
+        }
+        let linear = Color::rgba(0.5, 0.0, 1.0, 0.5).to_linear();
+        assert!((linear[0] - 0.214).abs() < 1e-3);
+        assert_eq!(linear[3], 0.5);
+    }
+}
@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+
+use glam::Vec2;
+use winit::dpi::PhysicalSize;
+use winit::window::{CursorGrabMode, Icon, WindowBuilder};
+
+use crate::bloom::BloomSettings;
+use crate::color::Color;
+use crate::renderer::InstanceUpload;
+
+// Source of the window icon.
+#[derive(Clone, Debug)]
+pub enum WindowIcon {
+    Rgba {
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    },
+    Path(PathBuf),
+}
+
+impl WindowIcon {
+    pub fn to_icon(&self) -> Option<Icon> {
+        let (rgba, width, height) = match self {
+            WindowIcon::Rgba {
+                width,
+                height,
+                data,
+            } => (data.clone(), *width, *height),
+            WindowIcon::Path(path) => match image::open(path) {
+                Ok(image) => {
+                    let image = image.to_rgba8();
+                    let (width, height) = image.dimensions();
+                    (image.into_raw(), width, height)
+                }
+                Err(err) => {
+                    log::warn!("Failed to load window icon {}: {}", path.display(), err);
+                    return None;
+                }
+            },
+        };
+        match Icon::from_rgba(rgba, width, height) {
+            Ok(icon) => Some(icon),
+            Err(err) => {
+                log::warn!("Invalid window icon: {}", err);
+                None
+            }
+        }
+    }
+}
+
+pub struct EngineConfig {
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+    pub vsync: bool,
+    // Overrides `vsync` with a specific mode, e.g. `Mailbox` for low latency.
+    pub present_mode: Option<wgpu::PresentMode>,
+    // sRGB like every other color the engine takes; see `Color`.
+    pub clear_color: [f32; 4],
+    // Top and bottom colors of a gradient drawn instead of `clear_color`.
+    pub background_gradient: Option<[[f32; 4]; 2]>,
+    // Multisample count for sprite edges: 1 (off), 2 or 4.
+    pub msaa_samples: u32,
+    // `LowPower` prefers an integrated GPU, which saves battery on laptops.
+    pub power_preference: wgpu::PowerPreference,
+    // Retry with the software adapter when no hardware adapter is available.
+    pub allow_fallback_adapter: bool,
+    // How sprite instances reach the GPU; see `InstanceUpload`.
+    pub instance_upload: InstanceUpload,
+    // Glow around bright pixels; None (the default) skips the extra passes.
+    pub bloom: Option<BloomSettings>,
+    // Seeds the engine's `Rng`; the same seed replays the same random spawns.
+    pub seed: u64,
+    pub icon: Option<WindowIcon>,
+    pub cursor_visible: bool,
+    // Applied at startup; `Engine::set_cursor_grab` changes it at runtime.
+    pub cursor_grab: CursorGrabMode,
+    pub sim: SimConfig,
+}
+
+// Simulation and demo tuning.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimConfig {
+    // Edge length of the demo's player sprite; other demo sprites scale it.
+    pub sprite_size: f32,
+    // Half extents of the physics playfield around the origin.
+    pub world_bounds: Vec2,
+    // Seconds per fixed update; kept within 1 ms..1 s, see `with_fixed_rate`.
+    pub fixed_dt: f32,
+    // When set, bodies further than this outside `world_bounds` are despawned.
+    pub despawn_margin: Option<f32>,
+    // Tints cycled by the demo for the player and spawned sprites.
+    pub palette: Vec<Color>,
+}
+
+impl SimConfig {
+    // Sets the fixed update rate in steps per second, e.g. 30.0 or 120.0.
+    pub fn with_fixed_rate(mut self, hz: f32) -> Self {
+        self.fixed_dt = 1.0 / hz;
+        self
+    }
+
+    // Wraps around the palette; an empty palette gives white.
+    pub fn palette_color(&self, index: usize) -> Color {
+        if self.palette.is_empty() {
+            return Color::WHITE;
+        }
+        self.palette[index % self.palette.len()]
+    }
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            sprite_size: 128.0,
+            world_bounds: Vec2::new(520.0, 320.0),
+            fixed_dt: 1.0 / 60.0,
+            despawn_margin: None,
+            palette: vec![
+                Color::WHITE,
+                Color::rgb(0.95, 0.75, 0.65),
+                Color::rgb(0.65, 0.9, 0.7),
+                Color::rgb(0.6, 0.7, 0.95),
+                Color::rgb(0.95, 0.85, 0.5),
+                Color::rgb(0.85, 0.7, 0.95),
+            ],
+        }
+    }
+}
+
+impl EngineConfig {
+    pub fn requested_present_mode(&self) -> wgpu::PresentMode {
+        match self.present_mode {
+            Some(mode) => mode,
+            None if self.vsync => wgpu::PresentMode::Fifo,
+            None => wgpu::PresentMode::Mailbox,
+        }
+    }
+
+    pub fn window_builder(&self) -> WindowBuilder {
+        WindowBuilder::new()
+            .with_title(&self.title)
+            .with_inner_size(PhysicalSize::new(self.width.max(1), self.height.max(1)))
+            .with_window_icon(self.icon.as_ref().and_then(WindowIcon::to_icon))
+    }
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+            title: "engine2d".to_string(),
+            vsync: true,
+            present_mode: None,
+            clear_color: [0.08, 0.1, 0.12, 1.0],
+            background_gradient: None,
+            msaa_samples: 4,
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            allow_fallback_adapter: true,
+            instance_upload: InstanceUpload::default(),
+            bloom: None,
+            seed: 0x5EED,
+            icon: None,
+            cursor_visible: true,
+            cursor_grab: CursorGrabMode::None,
+            sim: SimConfig::default(),
+        }
+    }
+}
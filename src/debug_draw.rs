@@ -0,0 +1,124 @@
+use glam::Vec2;
+
+use crate::color::Color;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: [f32; 2],
+    // Linear; `DebugDraw` converts from sRGB.
+    pub color: [f32; 4],
+}
+
+impl LineVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+    pub(crate) fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+const MIN_GRID_LINE_PIXELS: f32 = 8.0;
+
+// A world-space reference grid aligned to the origin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridStyle {
+    pub spacing: f32,
+    pub major_every: u32,
+    pub minor_color: [f32; 4],
+    pub major_color: [f32; 4],
+    pub axis_color: [f32; 4],
+}
+
+impl Default for GridStyle {
+    fn default() -> Self {
+        Self {
+            spacing: 32.0,
+            major_every: 8,
+            minor_color: [1.0, 1.0, 1.0, 0.08],
+            major_color: [1.0, 1.0, 1.0, 0.2],
+            axis_color: [1.0, 0.9, 0.3, 0.5],
+        }
+    }
+}
+
+// World-space lines collected during a frame and drawn over the sprites.
+#[derive(Default)]
+pub struct DebugDraw {
+    vertices: Vec<LineVertex>,
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn line(&mut self, a: Vec2, b: Vec2, color: [f32; 4]) {
+        let color = Color::from(color).to_linear();
+        self.vertices.push(LineVertex {
+            position: [a.x, a.y],
+            color,
+        });
+        self.vertices.push(LineVertex {
+            position: [b.x, b.y],
+            color,
+        });
+    }
+
+    // Axis-aligned wireframe rectangle.
+    pub fn rect(&mut self, min: Vec2, max: Vec2, color: [f32; 4]) {
+        let top_left = Vec2::new(min.x, max.y);
+        let bottom_right = Vec2::new(max.x, min.y);
+        self.line(min, bottom_right, color);
+        self.line(bottom_right, max, color);
+        self.line(max, top_left, color);
+        self.line(top_left, min, color);
+    }
+
+    // Covers the world rectangle `min`..`max`, thinning lines as `pixels_per_unit` drops.
+    pub fn grid(&mut self, min: Vec2, max: Vec2, style: &GridStyle, pixels_per_unit: f32) {
+        if !(style.spacing > 0.0 && pixels_per_unit > 0.0) {
+            return;
+        }
+        let major_every = style.major_every.max(1) as i64;
+        let coarsen = major_every.max(2);
+        let mut stride: i64 = 1;
+        while style.spacing * stride as f32 * pixels_per_unit < MIN_GRID_LINE_PIXELS {
+            match stride.checked_mul(coarsen) {
+                Some(next) => stride = next,
+                None => return,
+            }
+        }
+        let color = |line: i64| {
+            if line == 0 {
+                style.axis_color
+            } else if line % major_every == 0 {
+                style.major_color
+            } else {
+                style.minor_color
+            }
+        };
+        let step = style.spacing * stride as f32;
+        for i in (min.x / step).ceil() as i64..=(max.x / step).floor() as i64 {
+            let x = (i * stride) as f32 * style.spacing;
+            self.line(Vec2::new(x, min.y), Vec2::new(x, max.y), color(i * stride));
+        }
+        for i in (min.y / step).ceil() as i64..=(max.y / step).floor() as i64 {
+            let y = (i * stride) as f32 * style.spacing;
+            self.line(Vec2::new(min.x, y), Vec2::new(max.x, y), color(i * stride));
+        }
+    }
+
+    pub fn vertices(&self) -> &[LineVertex] {
+        &self.vertices
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+}
@@ -1,303 +1,1233 @@
+use std::path::Path;
 use std::sync::Arc;
 
 use glam::Vec2;
+use winit::event::{MouseButton, MouseScrollDelta};
 use winit::keyboard::KeyCode;
-use winit::window::Window;
+use winit::window::{CursorGrabMode, Window};
 
 use crate::assets::Assets;
-use crate::input::InputState;
-use crate::renderer::{InstanceRaw, Renderer};
-use crate::scene::{Animation, Body, Entity, Sprite, Transform, World};
-use crate::time::Time;
-
-const SPRITE_SIZE: f32 = 128.0;
-const FIXED_DT: f32 = 1.0 / 60.0;
-const WORLD_BOUNDS: Vec2 = Vec2::new(520.0, 320.0);
-
-const PALETTE: [[f32; 4]; 6] = [
-    [1.0, 1.0, 1.0, 1.0],
-    [0.95, 0.75, 0.65, 1.0],
-    [0.65, 0.9, 0.7, 1.0],
-    [0.6, 0.7, 0.95, 1.0],
-    [0.95, 0.85, 0.5, 1.0],
-    [0.85, 0.7, 0.95, 1.0],
+use crate::bloom::BloomSettings;
+use crate::config::{EngineConfig, SimConfig};
+use crate::debug_draw::{DebugDraw, GridStyle};
+#[cfg(feature = "gamepad")]
+use crate::gamepad::GamepadPoller;
+use crate::input::{ActionMap, GamepadStick, InputPlayback, InputRecorder, InputState};
+use crate::renderer::{
+    InstanceRaw, MaterialError, RenderStats, Renderer, RendererError, Viewport, build_batches,
+    screen_to_world,
+};
+use crate::rng::Rng;
+use crate::scene::{
+    Animation, AnimationEvent, BlendMode, Body, CollisionEvent, Entity, Fade, MaterialId, Sprite,
+    Transform, TriggerEvent, World,
+};
+use crate::shapes::Shapes;
+use crate::time::{FrameStats, Time};
+#[cfg(feature = "notify")]
+use crate::watcher::AssetWatcher;
+
+const MAX_PHYSICS_SUBSTEPS: u32 = 8;
+const SLOW_MOTION_SCALE: f32 = 0.3;
+const SCREENSHOT_PATH: &str = "screenshot.png";
+const PIXELS_PER_SCROLL_LINE: f32 = 40.0;
+const ZOOM_PER_SCROLL_LINE: f32 = 1.1;
+const DEFAULT_MIN_ZOOM: f32 = 0.25;
+const DEFAULT_MAX_ZOOM: f32 = 4.0;
+
+const HUD_TEXT_SCALE: f32 = 3.0;
+const HUD_TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+const DEBUG_BOUNDS_COLOR: [f32; 4] = [0.2, 1.0, 0.4, 1.0];
+const CURSOR_MARKER_RADIUS: f32 = 10.0;
+const CURSOR_MARKER_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.35];
+const BACKGROUND_COLORS: [[f32; 4]; 4] = [
+    [0.08, 0.1, 0.12, 1.0],
+    [0.2, 0.05, 0.06, 1.0],
+    [0.05, 0.14, 0.1, 1.0],
+    [0.12, 0.1, 0.2, 1.0],
 ];
 
-struct Camera {
-    position: Vec2,
-    zoom: f32,
+pub struct Camera {
+    pub position: Vec2,
+    previous_position: Vec2,
+    pub zoom: f32,
+    min_zoom: f32,
+    max_zoom: f32,
+    pub follow: Option<Entity>,
+    pub follow_lerp: f32,
+    // (min, max) for the camera position; None, the default, leaves it free.
+    pub bounds: Option<(Vec2, Vec2)>,
+    // Renders from the nearest whole pixel to stop pixel art shimmering.
+    pub pixel_snap: bool,
+    trauma: f32,
+    // Trauma lost per second.
+    pub shake_decay: f32,
+    pub shake_max_offset: f32,
+    pub shake_max_roll: f32,
+    shake_time: f32,
 }
 
 impl Camera {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             position: Vec2::ZERO,
+            previous_position: Vec2::ZERO,
             zoom: 1.0,
+            min_zoom: DEFAULT_MIN_ZOOM,
+            max_zoom: DEFAULT_MAX_ZOOM,
+            follow: None,
+            follow_lerp: 4.0,
+            bounds: None,
+            pixel_snap: false,
+            trauma: 0.0,
+            shake_decay: 1.5,
+            shake_max_offset: 24.0,
+            shake_max_roll: 0.06,
+            shake_time: 0.0,
+        }
+    }
+
+    pub fn min_zoom(&self) -> f32 {
+        self.min_zoom
+    }
+
+    pub fn max_zoom(&self) -> f32 {
+        self.max_zoom
+    }
+
+    // Ignored with a warning unless 0 < min <= max.
+    pub fn set_zoom_limits(&mut self, min: f32, max: f32) {
+        if !(min > 0.0 && min <= max && max.is_finite()) {
+            log::warn!(
+                "Invalid camera zoom limits {}..{}, keeping the current ones",
+                min,
+                max
+            );
+            return;
+        }
+        self.min_zoom = min;
+        self.max_zoom = max;
+        self.zoom = self.clamp_zoom(self.zoom);
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = self.clamp_zoom(zoom);
+    }
+
+    pub fn clamp_zoom(&self, zoom: f32) -> f32 {
+        zoom.clamp(self.min_zoom, self.max_zoom)
+    }
+
+    // Sets the zoom while keeping the world point `anchor` fixed on screen.
+    pub fn zoom_around(&mut self, zoom: f32, anchor: Vec2) {
+        let zoom = self.clamp_zoom(zoom);
+        let position = zoom_around_position(self.position, self.zoom, zoom, anchor);
+        self.previous_position += position - self.position;
+        self.position = position;
+        self.zoom = zoom;
+        self.clamp_to_bounds();
+    }
+
+    pub fn add_shake(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0.0, 1.0);
+    }
+
+    fn update_shake(&mut self, dt: f32) {
+        self.trauma = (self.trauma - self.shake_decay * dt).max(0.0);
+        self.shake_time += dt;
+    }
+
+    fn shake(&self) -> (Vec2, f32) {
+        let strength = self.trauma * self.trauma;
+        if strength <= 0.0 {
+            return (Vec2::ZERO, 0.0);
+        }
+        let offset = Vec2::new(
+            shake_noise(self.shake_time, 0.0),
+            shake_noise(self.shake_time, 1.0),
+        );
+        let roll = shake_noise(self.shake_time, 2.0);
+        (
+            offset * strength * self.shake_max_offset,
+            roll * strength * self.shake_max_roll,
+        )
+    }
+
+    fn move_towards(&mut self, target: Vec2, dt: f32) {
+        let t = 1.0 - (-self.follow_lerp * dt).exp();
+        self.position = self.position.lerp(target, t);
+    }
+
+    fn interpolated_position(&self, alpha: f32) -> Vec2 {
+        self.previous_position.lerp(self.position, alpha)
+    }
+
+    fn clamp_to_bounds(&mut self) {
+        if let Some((min, max)) = self.bounds {
+            self.position = self.position.clamp(min, max);
         }
     }
 }
 
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Camera position that keeps `anchor` on the same pixel when zooming.
+pub fn zoom_around_position(position: Vec2, zoom: f32, new_zoom: f32, anchor: Vec2) -> Vec2 {
+    if new_zoom <= 0.0 {
+        return position;
+    }
+    anchor - (anchor - position) * (zoom / new_zoom)
+}
+
+fn shake_noise(time: f32, seed: f32) -> f32 {
+    let t = time * 25.0 + seed * 17.3;
+    (t.sin() * 0.6 + (t * 2.3 + 1.7).sin() * 0.3 + (t * 5.1 + 4.1).sin() * 0.1).clamp(-1.0, 1.0)
+}
+
+fn default_actions() -> ActionMap {
+    let mut actions = ActionMap::new();
+    let bindings = [
+        ("move_left", KeyCode::ArrowLeft),
+        ("move_right", KeyCode::ArrowRight),
+        ("move_up", KeyCode::ArrowUp),
+        ("move_down", KeyCode::ArrowDown),
+        ("pan_left", KeyCode::KeyA),
+        ("pan_right", KeyCode::KeyD),
+        ("pan_up", KeyCode::KeyW),
+        ("pan_down", KeyCode::KeyS),
+        ("rotate_ccw", KeyCode::KeyZ),
+        ("rotate_cw", KeyCode::KeyX),
+        ("follow", KeyCode::KeyF),
+        ("tint", KeyCode::KeyC),
+        ("spawn", KeyCode::KeyN),
+        ("reset", KeyCode::Space),
+        ("pause", KeyCode::KeyP),
+        ("step", KeyCode::Period),
+        ("slow_motion", KeyCode::KeyT),
+        ("help", KeyCode::KeyH),
+        ("screenshot", KeyCode::F12),
+        ("debug_draw", KeyCode::F3),
+        ("wireframe", KeyCode::F4),
+        ("culling", KeyCode::F5),
+        ("bloom", KeyCode::F6),
+        ("grid", KeyCode::F2),
+        ("shake", KeyCode::KeyK),
+        ("grab_cursor", KeyCode::KeyG),
+        ("background", KeyCode::KeyB),
+    ];
+    for (action, key) in bindings {
+        actions.bind(action, key);
+    }
+    actions
+}
+
+struct DrawItem {
+    layer: i32,
+    z: f32,
+    order: i64,
+    texture_id: u32,
+    blend: BlendMode,
+    material: MaterialId,
+    instance: InstanceRaw,
+}
+
+fn overlaps_view(bounds: (Vec2, Vec2), view: (Vec2, Vec2)) -> bool {
+    bounds.0.cmple(view.1).all() && bounds.1.cmpge(view.0).all()
+}
+
+fn sort_back_to_front(items: &mut [DrawItem]) {
+    items.sort_by(|a, b| {
+        a.layer
+            .cmp(&b.layer)
+            .then(a.z.total_cmp(&b.z))
+            .then(a.order.cmp(&b.order))
+    });
+}
+
+// The subsystems a fixed-update callback may touch.
+pub struct UpdateContext<'a> {
+    pub world: &'a mut World,
+    pub camera: &'a mut Camera,
+    pub input: &'a InputState,
+    pub actions: &'a ActionMap,
+    pub assets: &'a Assets,
+    pub rng: &'a mut Rng,
+    // The current simulation settings, e.g. the palette and world bounds.
+    pub sim: &'a SimConfig,
+    // Written back to the renderer after the callback when changed.
+    pub clear_color: &'a mut [f32; 4],
+}
+
+type FixedUpdateFn = Box<dyn FnMut(&mut UpdateContext, f32)>;
+
 pub struct Engine {
+    window: Arc<Window>,
     renderer: Renderer,
     assets: Assets,
     world: World,
     input: InputState,
+    actions: ActionMap,
     time: Time,
     camera: Camera,
-    player: Entity,
+    draw_list: Vec<DrawItem>,
     instance_data: Vec<InstanceRaw>,
-    paused: bool,
-    player_color_index: usize,
-    spawn_counter: u32,
+    collision_events: Vec<CollisionEvent>,
+    animation_events: Vec<(Entity, AnimationEvent)>,
+    trigger_events: Vec<TriggerEvent>,
+    #[cfg(feature = "gamepad")]
+    gamepad: Option<GamepadPoller>,
+    #[cfg(feature = "notify")]
+    asset_watcher: Option<AssetWatcher>,
+    debug_draw: DebugDraw,
+    shapes: Shapes,
+    show_sprite_bounds: bool,
+    show_grid: bool,
+    grid_style: GridStyle,
+    clips_version: u32,
+    fixed_update_fn: FixedUpdateFn,
+    rng: Rng,
+    playback: Option<InputPlayback>,
+    step_once: bool,
+    cursor_grab: CursorGrabMode,
+    clear_color: [f32; 4],
+    frustum_culling: bool,
+    culled_sprites: usize,
+    sim: SimConfig,
 }
 
 impl Engine {
-    pub async fn new(window: Arc<Window>) -> Self {
-        let mut renderer = Renderer::new(window).await;
+    pub async fn new(window: Arc<Window>, config: &EngineConfig) -> Result<Self, RendererError> {
+        let mut renderer = Renderer::new(window.clone(), config).await?;
         let assets = Assets::load(renderer.device(), renderer.queue());
-        renderer.set_texture(&assets.texture);
+        renderer.set_textures(assets.textures());
+        if let Some(source) = assets.sprite_shader()
+            && let Err(err) = renderer.set_sprite_shader(Some(&source))
+        {
+            log::warn!("Using the built-in sprite shader: {}", err);
+        }
 
+        let mut sim = config.sim.clone();
+        let sprite_size = sim.sprite_size;
         let mut world = World::new();
-        let player = world.spawn_sprite(
-            Transform::new(Vec2::ZERO),
-            Sprite {
-                size: Vec2::splat(SPRITE_SIZE),
-                tile_index: 0,
-                color: PALETTE[0],
-                spin: 0.0,
-                animation: None,
-            },
-        );
+        world.set_max_substeps(MAX_PHYSICS_SUBSTEPS);
+        let player = world
+            .build()
+            .sprite(Sprite::new(
+                Vec2::splat(sprite_size),
+                0,
+                sim.palette_color(0),
+            ))
+            .spawn();
 
-        let child = world.spawn_sprite(
-            Transform::new(Vec2::new(0.0, SPRITE_SIZE * 0.7)),
-            Sprite {
-                size: Vec2::splat(SPRITE_SIZE * 0.35),
-                tile_index: 1,
-                color: PALETTE[5],
+        world
+            .build()
+            .at(Vec2::new(0.0, sprite_size * 0.7))
+            .sprite(Sprite {
                 spin: 1.2,
-                animation: None,
-            },
-        );
-        world.set_parent(child, player);
+                layer: 1,
+                ..Sprite::new(Vec2::splat(sprite_size * 0.35), 1, sim.palette_color(5))
+            })
+            .parent(player)
+            .spawn();
 
-        world.spawn_sprite_with_body(
-            Transform {
-                position: Vec2::new(220.0, -80.0),
-                rotation: 0.0,
-                scale: Vec2::ONE,
-            },
-            Sprite {
-                size: Vec2::splat(SPRITE_SIZE * 0.75),
-                tile_index: 1,
-                color: PALETTE[2],
+        world
+            .build()
+            .at(Vec2::new(220.0, -80.0))
+            .sprite(Sprite {
                 spin: 0.6,
                 animation: Some(Animation::new(vec![0, 1, 2, 3], 6.0)),
-            },
-            Some(Body::new(Vec2::new(80.0, 140.0))),
-        );
+                ..Sprite::new(Vec2::splat(sprite_size * 0.75), 1, sim.palette_color(2))
+            })
+            .body(Body::new(Vec2::new(80.0, 140.0)))
+            .spawn();
 
-        world.spawn_sprite_with_body(
-            Transform {
-                position: Vec2::new(-240.0, 140.0),
-                rotation: 0.0,
-                scale: Vec2::ONE,
-            },
-            Sprite {
-                size: Vec2::splat(SPRITE_SIZE * 0.9),
-                tile_index: 2,
-                color: PALETTE[3],
+        world
+            .build()
+            .at(Vec2::new(-240.0, 140.0))
+            .sprite(Sprite {
                 spin: -0.4,
-                animation: None,
-            },
-            Some(Body::new(Vec2::new(-120.0, 60.0))),
-        );
+                ..Sprite::new(Vec2::splat(sprite_size * 0.9), 2, sim.palette_color(3))
+            })
+            .body(Body::new(Vec2::new(-120.0, 60.0)))
+            .spawn();
 
-        world.spawn_sprite_with_body(
-            Transform {
-                position: Vec2::new(-100.0, -200.0),
-                rotation: 0.0,
-                scale: Vec2::ONE,
-            },
-            Sprite {
-                size: Vec2::splat(SPRITE_SIZE * 0.6),
-                tile_index: 3,
-                color: PALETTE[4],
+        world
+            .build()
+            .at(Vec2::new(-100.0, -200.0))
+            .sprite(Sprite {
                 spin: 0.2,
-                animation: None,
-            },
-            Some(Body::new(Vec2::new(140.0, -90.0))),
-        );
+                ..Sprite::new(Vec2::splat(sprite_size * 0.6), 3, sim.palette_color(4))
+            })
+            .body(Body::new(Vec2::new(140.0, -90.0)))
+            .spawn();
 
         let camera = Camera::new();
-        renderer.update_camera(camera.position, camera.zoom);
+        renderer.update_camera(camera.position, camera.zoom, camera.pixel_snap);
 
-        Self {
+        let time = Time::new(sim.fixed_dt, &config.title);
+        sim.fixed_dt = time.fixed_dt();
+
+        #[cfg(feature = "notify")]
+        let asset_watcher = AssetWatcher::new(&assets.watched_paths());
+        let mut demo = Demo {
+            player,
+            player_color_index: 0,
+            spawn_counter: 4,
+            background_index: 0,
+        };
+
+        let mut engine = Self {
+            window,
             renderer,
             assets,
             world,
             input: InputState::new(),
-            time: Time::new(FIXED_DT),
+            actions: default_actions(),
+            time,
             camera,
-            player,
+            draw_list: Vec::new(),
             instance_data: Vec::new(),
-            paused: false,
-            player_color_index: 0,
-            spawn_counter: 4,
-        }
+            collision_events: Vec::new(),
+            animation_events: Vec::new(),
+            trigger_events: Vec::new(),
+            #[cfg(feature = "gamepad")]
+            gamepad: GamepadPoller::new(),
+            #[cfg(feature = "notify")]
+            asset_watcher,
+            debug_draw: DebugDraw::new(),
+            shapes: Shapes::new(),
+            show_sprite_bounds: false,
+            show_grid: false,
+            grid_style: GridStyle::default(),
+            clips_version: 0,
+            fixed_update_fn: Box::new(move |ctx, dt| demo.update(ctx, dt)),
+            rng: Rng::new(config.seed),
+            playback: None,
+            step_once: false,
+            cursor_grab: CursorGrabMode::None,
+            clear_color: config.clear_color,
+            frustum_culling: true,
+            culled_sprites: 0,
+            sim,
+        };
+        engine.set_cursor_visible(config.cursor_visible);
+        engine.set_cursor_grab(config.cursor_grab);
+        Ok(engine)
     }
 
     pub fn window(&self) -> &Window {
-        self.renderer.window()
+        &self.window
+    }
+
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.window.set_cursor_visible(visible);
+    }
+
+    // Falls back to the other grab mode; returns false when neither works.
+    pub fn set_cursor_grab(&mut self, mode: CursorGrabMode) -> bool {
+        let fallback = match mode {
+            CursorGrabMode::Confined => Some(CursorGrabMode::Locked),
+            CursorGrabMode::Locked => Some(CursorGrabMode::Confined),
+            CursorGrabMode::None => None,
+        };
+        let mut applied = mode;
+        let mut result = self.window.set_cursor_grab(mode);
+        if result.is_err()
+            && let Some(fallback) = fallback
+        {
+            applied = fallback;
+            result = self.window.set_cursor_grab(fallback);
+        }
+        match result {
+            Ok(()) => {
+                if applied != mode {
+                    log::info!("Cursor grab {:?} unsupported, using {:?}", mode, applied);
+                }
+                self.cursor_grab = applied;
+                true
+            }
+            Err(err) => {
+                log::warn!("Could not set cursor grab {:?}: {}", mode, err);
+                false
+            }
+        }
+    }
+
+    pub fn cursor_grab(&self) -> CursorGrabMode {
+        self.cursor_grab
+    }
+
+    // Winit reports the new physical size separately, through `resize`.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.renderer.set_scale_factor(scale_factor as f32);
+        self.renderer.update_camera(
+            self.camera.position,
+            self.camera.zoom,
+            self.camera.pixel_snap,
+        );
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.renderer.scale_factor()
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.renderer.resize(new_size);
-        self.renderer.update_camera(self.camera.position, self.camera.zoom);
+        self.renderer.update_camera(
+            self.camera.position,
+            self.camera.zoom,
+            self.camera.pixel_snap,
+        );
+    }
+
+    // While following, manual panning is disabled.
+    pub fn set_camera_follow(&mut self, target: Option<Entity>) {
+        self.camera.follow = target;
+    }
+
+    pub fn set_camera_bounds(&mut self, bounds: Option<(Vec2, Vec2)>) {
+        self.camera.bounds = bounds;
+        self.camera.clamp_to_bounds();
+    }
+
+    // Replaces the game logic run every fixed step, including the demo controls.
+    pub fn set_fixed_update(&mut self, callback: impl FnMut(&mut UpdateContext, f32) + 'static) {
+        self.fixed_update_fn = Box::new(callback);
+    }
+
+    pub fn time(&self) -> &Time {
+        &self.time
+    }
+
+    pub fn set_on_frame_stats(&mut self, callback: impl FnMut(FrameStats) + 'static) {
+        self.time.set_on_stats(callback);
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    // For gameplay code to spawn, despawn and query entities between frames.
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn sim_config(&self) -> &SimConfig {
+        &self.sim
+    }
+
+    // Changes the fixed update rate at runtime, in steps per second.
+    pub fn set_fixed_rate(&mut self, hz: f32) {
+        self.sim.fixed_dt = self.time.set_fixed_dt(1.0 / hz);
+    }
+
+    // Resizes the physics playfield; the camera clamp is left alone.
+    pub fn set_world_bounds(&mut self, bounds: Vec2) {
+        self.sim.world_bounds = bounds;
+    }
+
+    pub fn set_frustum_culling(&mut self, enabled: bool) {
+        self.frustum_culling = enabled;
+    }
+
+    pub fn frustum_culling(&self) -> bool {
+        self.frustum_culling
+    }
+
+    // None turns bloom off; the bloom key toggles it with default settings.
+    pub fn set_bloom(&mut self, settings: Option<BloomSettings>) {
+        self.renderer.set_bloom(settings);
+    }
+
+    pub fn bloom(&self) -> Option<BloomSettings> {
+        self.renderer.bloom()
+    }
+
+    // A world-space grid over the visible area; the grid key toggles it too.
+    pub fn set_show_grid(&mut self, show: bool) {
+        self.show_grid = show;
+    }
+
+    pub fn show_grid(&self) -> bool {
+        self.show_grid
+    }
+
+    pub fn set_grid_style(&mut self, style: GridStyle) {
+        self.grid_style = style;
+    }
+
+    // Sprites left out of the last redraw by frustum culling.
+    pub fn culled_sprites(&self) -> usize {
+        self.culled_sprites
+    }
+
+    // Instances, draw calls and uploads of the last presented frame.
+    pub fn render_stats(&self) -> RenderStats {
+        self.renderer.stats()
+    }
+
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        self.renderer.adapter_info()
+    }
+
+    pub fn limits(&self) -> &wgpu::Limits {
+        self.renderer.limits()
+    }
+
+    // Split-screen views; see `Renderer::set_viewports`.
+    pub fn set_viewports(&mut self, viewports: &[Viewport]) {
+        self.renderer.set_viewports(viewports);
+    }
+
+    // See `Renderer::register_material`.
+    pub fn register_material(&mut self, wgsl_source: &str) -> Result<MaterialId, MaterialError> {
+        self.renderer.register_material(wgsl_source)
+    }
+
+    // Takes effect from the next render and replaces any background gradient.
+    pub fn set_clear_color(&mut self, color: [f32; 4]) {
+        self.clear_color = color;
+        self.renderer.set_clear_color(color);
+    }
+
+    pub fn clear_color(&self) -> [f32; 4] {
+        self.clear_color
+    }
+
+    // Adds screen-shake trauma (clamped to 1.0 in total), which then decays.
+    pub fn add_camera_shake(&mut self, trauma: f32) {
+        self.camera.add_shake(trauma);
+    }
+
+    pub fn actions_mut(&mut self) -> &mut ActionMap {
+        &mut self.actions
     }
 
     pub fn handle_key(&mut self, code: KeyCode, pressed: bool) {
-        self.input.set_key(code, pressed);
+        if self.playback.is_none() {
+            self.input.set_key(code, pressed);
+        }
+    }
+
+    pub fn start_input_recording(&mut self) {
+        self.input.start_recording();
+    }
+
+    pub fn stop_input_recording(&mut self) -> Option<InputRecorder> {
+        self.input.stop_recording()
+    }
+
+    // Replays the recorded keys from the next frame on, ignoring the keyboard.
+    pub fn play_input(&mut self, recording: InputRecorder) {
+        self.playback = Some(InputPlayback::new(recording));
+    }
+
+    pub fn is_playing_input(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    pub fn handle_mouse_button(&mut self, button: MouseButton, pressed: bool) {
+        self.input.set_mouse_button(button, pressed);
+    }
+
+    pub fn handle_cursor_moved(&mut self, position: winit::dpi::PhysicalPosition<f64>) {
+        self.input
+            .set_cursor_position(Vec2::new(position.x as f32, position.y as f32));
+    }
+
+    // Call once per frame before `redraw`.
+    #[cfg(feature = "gamepad")]
+    pub fn poll_gamepad(&mut self) {
+        if let Some(gamepad) = &mut self.gamepad {
+            gamepad.poll(&mut self.input);
+        }
+    }
+
+    pub fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => {
+                position.y as f32 / (PIXELS_PER_SCROLL_LINE * self.renderer.scale_factor())
+            }
+        };
+        self.input.add_scroll(lines);
+    }
+
+    // Lines drawn over the sprites on the next redraw, then cleared.
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        &mut self.debug_draw
+    }
+
+    // Shapes drawn on the next redraw, then cleared.
+    pub fn shapes(&mut self) -> &mut Shapes {
+        &mut self.shapes
+    }
+
+    // Collisions reported by every fixed step of the most recent frame.
+    pub fn collision_events(&self) -> &[CollisionEvent] {
+        &self.collision_events
+    }
+
+    // Trigger enter/exit events from every fixed step of the most recent frame.
+    pub fn trigger_events(&self) -> &[TriggerEvent] {
+        &self.trigger_events
+    }
+
+    // Animation events from every fixed step of the most recent frame.
+    pub fn animation_events(&self) -> &[(Entity, AnimationEvent)] {
+        &self.animation_events
     }
 
     pub fn redraw(&mut self) -> Result<(), wgpu::SurfaceError> {
         let dt = self.time.advance();
-        self.time.update_fps(dt, self.renderer.window(), self.paused);
+        self.collision_events.clear();
+        self.animation_events.clear();
+        self.trigger_events.clear();
+        self.time.update_fps(dt, &self.window);
 
-        let steps = self.time.consume_fixed_steps();
+        if let Some(playback) = &mut self.playback {
+            playback.apply_frame(&mut self.input);
+            if playback.is_finished() {
+                log::info!("Input playback finished");
+                self.playback = None;
+            }
+        }
+
+        if self.time.is_paused() && self.actions.is_action_just_pressed(&self.input, "step") {
+            self.step_once = true;
+        }
+
+        let mut steps = self.time.consume_fixed_steps();
+        if self.step_once {
+            steps = steps.max(1);
+        }
         for _ in 0..steps {
-            self.fixed_update(self.time.fixed_dt());
+            let (dt, paused) = if self.step_once {
+                self.step_once = false;
+                (self.time.fixed_dt(), false)
+            } else {
+                (self.time.scaled_fixed_dt(), self.time.is_paused())
+            };
+            self.input.begin_fixed_step();
+            self.fixed_update(dt, paused);
+            self.input.end_fixed_step();
         }
 
-        if self
-            .assets
-            .reload_if_changed(self.renderer.device(), self.renderer.queue())
-        {
-            self.renderer.set_texture(&self.assets.texture);
+        let scroll = self.input.scroll_delta();
+        if scroll != 0.0 {
+            let zoom = self.camera.zoom * ZOOM_PER_SCROLL_LINE.powf(scroll);
+            let anchor = screen_to_world(
+                self.renderer.size(),
+                self.camera.position,
+                self.camera.zoom * self.renderer.scale_factor(),
+                self.input.cursor_position(),
+            );
+            self.camera.zoom_around(zoom, anchor);
         }
 
-        self.renderer.update_camera(self.camera.position, self.camera.zoom);
+        if self.reload_changed_assets() {
+            self.renderer.set_textures(self.assets.textures());
+        }
+        if self.assets.config_version() != self.clips_version {
+            self.clips_version = self.assets.config_version();
+            self.world.refresh_clips(&self.assets);
+        }
+
+        let alpha = self.time.interpolation_alpha();
+        self.camera.update_shake(dt);
+        let (shake_offset, shake_roll) = self.camera.shake();
+        let camera_position = self.camera.interpolated_position(alpha) + shake_offset;
+        self.renderer
+            .update_camera(camera_position, self.camera.zoom, self.camera.pixel_snap);
+        self.renderer.set_camera_roll(shake_roll);
+        self.draw_list.clear();
+        self.culled_sprites = 0;
+        let (view_min, view_max) = self.renderer.visible_world_rect();
+        if self.show_grid {
+            self.debug_draw
+                .grid(view_min, view_max, &self.grid_style, self.camera.zoom);
+        }
+        self.world
+            .for_each_sprite_world_ordered(alpha, |_, transform, sprite| {
+                let atlas = self.assets.atlas(sprite.texture_id);
+                let transform = &Transform {
+                    position: transform.position + sprite.parallax_offset(camera_position),
+                    ..*transform
+                };
+                let (min, max) = sprite.world_aabb(transform);
+                if self.frustum_culling && !overlaps_view((min, max), (view_min, view_max)) {
+                    self.culled_sprites += 1;
+                    return;
+                }
+                if self.show_sprite_bounds {
+                    self.debug_draw.rect(min, max, DEBUG_BOUNDS_COLOR);
+                }
+                let mut push = |instance| {
+                    self.draw_list.push(DrawItem {
+                        layer: sprite.layer,
+                        z: transform.z,
+                        order: sprite.order,
+                        texture_id: sprite.texture_id,
+                        blend: sprite.blend,
+                        material: sprite.material,
+                        instance,
+                    })
+                };
+                match sprite.nine_slice {
+                    Some(slice) => InstanceRaw::nine_slice(transform, sprite, atlas, slice)
+                        .into_iter()
+                        .for_each(push),
+                    None => push(InstanceRaw::from_components(transform, sprite, atlas)),
+                }
+            });
+        for tilemap in self
+            .world
+            .tilemaps()
+            .iter()
+            .filter(|tilemap| tilemap.visible)
+        {
+            let atlas = self.assets.atlas(tilemap.texture_id);
+            let (min, max) = if self.frustum_culling {
+                (view_min, view_max)
+            } else {
+                tilemap.bounds()
+            };
+            tilemap.for_each_tile_in(min, max, |center, tile_index| {
+                self.draw_list.push(DrawItem {
+                    layer: tilemap.layer,
+                    z: 0.0,
+                    order: tilemap.order,
+                    texture_id: tilemap.texture_id,
+                    blend: BlendMode::Alpha,
+                    material: MaterialId::DEFAULT,
+                    instance: tilemap.instance(center, tile_index, atlas),
+                })
+            });
+        }
+        sort_back_to_front(&mut self.draw_list);
         self.instance_data.clear();
-        self.world.for_each_sprite_world(|_, transform, sprite| {
-            self.instance_data
-                .push(InstanceRaw::from_components(transform, sprite, &self.assets.atlas));
-        });
-        self.renderer.update_instances(&self.instance_data);
+        self.instance_data
+            .extend(self.draw_list.iter().map(|item| item.instance));
+        let batches = build_batches(
+            self.draw_list
+                .iter()
+                .map(|item| (item.texture_id, item.blend, item.material)),
+        );
+        self.renderer
+            .update_instances(&self.instance_data, &batches);
+        self.renderer.update_debug_lines(self.debug_draw.vertices());
+        self.debug_draw.clear();
 
+        let cursor = self.renderer.screen_to_world(self.input.cursor_position());
+        self.shapes
+            .circle(cursor, CURSOR_MARKER_RADIUS, CURSOR_MARKER_COLOR);
+        self.renderer.update_shapes(self.shapes.instances());
+        self.shapes.clear();
+
+        let hud_origin = self.renderer.screen_to_world(Vec2::splat(8.0));
+        let mut hud = format!("{:.0} FPS", self.time.fps());
+        if self.show_sprite_bounds {
+            let stats = self.renderer.stats();
+            hud.push_str(&format!(
+                "\n{} INSTANCES\n{} DRAWS\n{} KB UPLOADED\n{} CULLED",
+                stats.instances,
+                stats.draw_calls,
+                stats.bytes_uploaded.div_ceil(1024),
+                self.culled_sprites
+            ));
+        }
+        self.renderer.draw_text(
+            &hud,
+            hud_origin,
+            HUD_TEXT_SCALE / self.camera.zoom,
+            HUD_TEXT_COLOR,
+        );
+
+        if self
+            .actions
+            .is_action_just_pressed(&self.input, "screenshot")
+        {
+            match self.renderer.capture_screenshot(Path::new(SCREENSHOT_PATH)) {
+                Ok(()) => log::info!("Saved screenshot to {}", SCREENSHOT_PATH),
+                Err(err) => log::warn!("Failed to save screenshot: {}", err),
+            }
+        }
         let result = self.renderer.render();
         self.input.finish_frame();
         result
     }
 
-    fn fixed_update(&mut self, dt: f32) {
-        if self.input.is_just_pressed(KeyCode::KeyP) {
-            self.paused = !self.paused;
+    fn reload_changed_assets(&mut self) -> bool {
+        #[cfg(feature = "notify")]
+        if let Some(watcher) = &mut self.asset_watcher {
+            let mut changed = false;
+            let notified = watcher.take_changed();
+            if notified {
+                changed = self
+                    .assets
+                    .reload(self.renderer.device(), self.renderer.queue());
+                watcher.watch_paths(&self.assets.watched_paths());
+            }
+            if notified && self.assets.sprite_shader_changed() {
+                self.reload_sprite_shader();
+            }
+            let loaded = self
+                .assets
+                .poll_loads(self.renderer.device(), self.renderer.queue());
+            return changed || loaded;
+        }
+
+        if self.assets.sprite_shader_changed() {
+            self.reload_sprite_shader();
+        }
+        self.assets
+            .reload_if_changed(self.renderer.device(), self.renderer.queue())
+    }
+
+    fn reload_sprite_shader(&mut self) {
+        let source = self.assets.sprite_shader();
+        match self.renderer.set_sprite_shader(source.as_deref()) {
+            Ok(()) => log::info!("Reloaded sprite shader"),
+            Err(err) => log::warn!("Keeping the previous sprite shader: {}", err),
+        }
+    }
+
+    fn fixed_update(&mut self, dt: f32, paused: bool) {
+        self.world.store_previous_transforms();
+        self.camera.previous_position = self.camera.position;
+
+        if self.actions.is_action_just_pressed(&self.input, "pause") {
+            self.time.set_paused(!self.time.is_paused());
+        }
+
+        if self
+            .actions
+            .is_action_just_pressed(&self.input, "slow_motion")
+        {
+            let scale = if self.time.time_scale() < 1.0 {
+                1.0
+            } else {
+                SLOW_MOTION_SCALE
+            };
+            self.time.set_time_scale(scale);
+        }
+
+        if self
+            .actions
+            .is_action_just_pressed(&self.input, "debug_draw")
+        {
+            self.show_sprite_bounds = !self.show_sprite_bounds;
+        }
+
+        if self
+            .actions
+            .is_action_just_pressed(&self.input, "wireframe")
+        {
+            let enabled = !self.renderer.wireframe();
+            self.renderer.set_wireframe(enabled);
+        }
+
+        if self.actions.is_action_just_pressed(&self.input, "grid") {
+            self.show_grid = !self.show_grid;
+        }
+
+        if self.actions.is_action_just_pressed(&self.input, "bloom") {
+            let settings = match self.renderer.bloom() {
+                Some(_) => None,
+                None => Some(BloomSettings::default()),
+            };
+            self.renderer.set_bloom(settings);
         }
 
-        if self.input.is_just_pressed(KeyCode::KeyH) {
+        if self.actions.is_action_just_pressed(&self.input, "culling") {
+            self.frustum_culling = !self.frustum_culling;
             log::info!(
-                "Controls: arrows move sprite, WASD pan, Q/E zoom, Z/X rotate, C tint, N spawn, Space reset, P pause"
+                "Frustum culling {}",
+                if self.frustum_culling { "on" } else { "off" }
             );
         }
 
-        if self.paused {
-            return;
+        if self
+            .actions
+            .is_action_just_pressed(&self.input, "grab_cursor")
+        {
+            let mode = if self.cursor_grab == CursorGrabMode::None {
+                CursorGrabMode::Confined
+            } else {
+                CursorGrabMode::None
+            };
+            self.set_cursor_grab(mode);
+        }
+
+        if self.actions.is_action_just_pressed(&self.input, "help") {
+            log::info!(
+                "Controls: arrows move sprite, WASD pan, F follow, mouse wheel zoom, Z/X rotate, C tint, B background, N spawn, K shake, G grab cursor, Space reset, P pause, . step while paused, T slow motion, F2 grid, F3 debug bounds, F4 wireframe, F5 culling, F6 bloom, F12 screenshot"
+            );
+        }
+
+        if !paused {
+            self.step_simulation(dt);
+        }
+        let animation_dt = if paused {
+            self.time.fixed_dt() * self.time.time_scale()
+        } else {
+            dt
+        };
+        let animation_events = self.world.update_animations(animation_dt, paused);
+        self.animation_events.extend(animation_events);
+    }
+
+    fn step_simulation(&mut self, dt: f32) {
+        let mut clear_color = self.clear_color;
+        (self.fixed_update_fn)(
+            &mut UpdateContext {
+                world: &mut self.world,
+                camera: &mut self.camera,
+                input: &self.input,
+                actions: &self.actions,
+                assets: &self.assets,
+                rng: &mut self.rng,
+                sim: &self.sim,
+                clear_color: &mut clear_color,
+            },
+            dt,
+        );
+        if clear_color != self.clear_color {
+            self.set_clear_color(clear_color);
+        }
+
+        let follow_target = self
+            .camera
+            .follow
+            .and_then(|entity| self.world.world_transform(entity));
+        if let Some(target) = follow_target {
+            self.camera.move_towards(target.position, dt);
+        }
+        self.camera.clamp_to_bounds();
+
+        let (collisions, destroyed) = self
+            .world
+            .step_physics_substepped(dt, self.sim.world_bounds);
+        self.collision_events.extend(collisions);
+        for entity in destroyed {
+            self.world.despawn(entity);
         }
+        if let Some(margin) = self.sim.despawn_margin {
+            self.world
+                .despawn_outside(self.sim.world_bounds + Vec2::splat(margin));
+        }
+        self.trigger_events
+            .extend_from_slice(self.world.trigger_events());
+    }
+}
+
+struct Demo {
+    player: Entity,
+    player_color_index: usize,
+    spawn_counter: u32,
+    background_index: usize,
+}
 
+impl Demo {
+    fn update(&mut self, ctx: &mut UpdateContext, dt: f32) {
         let move_speed = 300.0;
         let rotate_speed = 2.4;
 
         let mut sprite_dir = Vec2::ZERO;
-        if self.input.is_pressed(KeyCode::ArrowLeft) {
+        if ctx.actions.is_action_pressed(ctx.input, "move_left") {
             sprite_dir.x -= 1.0;
         }
-        if self.input.is_pressed(KeyCode::ArrowRight) {
+        if ctx.actions.is_action_pressed(ctx.input, "move_right") {
             sprite_dir.x += 1.0;
         }
-        if self.input.is_pressed(KeyCode::ArrowUp) {
+        if ctx.actions.is_action_pressed(ctx.input, "move_up") {
             sprite_dir.y += 1.0;
         }
-        if self.input.is_pressed(KeyCode::ArrowDown) {
+        if ctx.actions.is_action_pressed(ctx.input, "move_down") {
             sprite_dir.y -= 1.0;
         }
 
-        if let Some(transform) = self.world.get_transform_mut(self.player) {
-            if sprite_dir.length_squared() > 0.0 {
-                transform.position += sprite_dir.normalize() * move_speed * dt;
-            }
-            if self.input.is_pressed(KeyCode::KeyZ) {
+        let sprite_dir = (sprite_dir.normalize_or_zero()
+            + ctx.input.gamepad_stick(GamepadStick::Left))
+        .clamp_length_max(1.0);
+
+        if let Some(transform) = ctx.world.get_transform_mut(self.player) {
+            transform.position += sprite_dir * move_speed * dt;
+            if ctx.actions.is_action_pressed(ctx.input, "rotate_ccw") {
                 transform.rotation -= rotate_speed * dt;
             }
-            if self.input.is_pressed(KeyCode::KeyX) {
+            if ctx.actions.is_action_pressed(ctx.input, "rotate_cw") {
                 transform.rotation += rotate_speed * dt;
             }
         }
 
         let mut camera_dir = Vec2::ZERO;
-        if self.input.is_pressed(KeyCode::KeyA) {
+        if ctx.actions.is_action_pressed(ctx.input, "pan_left") {
             camera_dir.x -= 1.0;
         }
-        if self.input.is_pressed(KeyCode::KeyD) {
+        if ctx.actions.is_action_pressed(ctx.input, "pan_right") {
             camera_dir.x += 1.0;
         }
-        if self.input.is_pressed(KeyCode::KeyW) {
+        if ctx.actions.is_action_pressed(ctx.input, "pan_up") {
             camera_dir.y += 1.0;
         }
-        if self.input.is_pressed(KeyCode::KeyS) {
+        if ctx.actions.is_action_pressed(ctx.input, "pan_down") {
             camera_dir.y -= 1.0;
         }
-        if camera_dir.length_squared() > 0.0 {
-            self.camera.position += camera_dir.normalize() * move_speed * dt;
+        let camera_dir = (camera_dir.normalize_or_zero()
+            + ctx.input.gamepad_stick(GamepadStick::Right))
+        .clamp_length_max(1.0);
+        if ctx.actions.is_action_just_pressed(ctx.input, "follow") {
+            ctx.camera.follow = match ctx.camera.follow {
+                Some(_) => None,
+                None => Some(self.player),
+            };
         }
 
-        let zoom_speed = 1.5;
-        if self.input.is_pressed(KeyCode::KeyQ) {
-            self.camera.zoom = (self.camera.zoom * (1.0 + zoom_speed * dt)).min(4.0);
-        }
-        if self.input.is_pressed(KeyCode::KeyE) {
-            self.camera.zoom = (self.camera.zoom * (1.0 - zoom_speed * dt)).max(0.25);
+        if ctx.camera.follow.is_none() {
+            ctx.camera.position += camera_dir * move_speed * dt;
         }
 
-        if self.input.is_just_pressed(KeyCode::Space) {
-            self.camera.position = Vec2::ZERO;
-            self.camera.zoom = 1.0;
-            if let Some(transform) = self.world.get_transform_mut(self.player) {
+        if ctx.actions.is_action_just_pressed(ctx.input, "reset") {
+            ctx.camera.position = Vec2::ZERO;
+            ctx.camera.set_zoom(1.0);
+            if let Some(transform) = ctx.world.get_transform_mut(self.player) {
                 transform.position = Vec2::ZERO;
                 transform.rotation = 0.0;
             }
         }
 
-        if self.input.is_just_pressed(KeyCode::KeyC) {
-            self.player_color_index = (self.player_color_index + 1) % PALETTE.len();
-            if let Some(sprite) = self.world.get_sprite_mut(self.player) {
-                sprite.color = PALETTE[self.player_color_index];
+        if ctx.actions.is_action_just_pressed(ctx.input, "shake") {
+            ctx.camera.add_shake(0.5);
+        }
+
+        if ctx.actions.is_action_just_pressed(ctx.input, "tint") {
+            self.player_color_index = (self.player_color_index + 1) % ctx.sim.palette.len().max(1);
+            if let Some(sprite) = ctx.world.get_sprite_mut(self.player) {
+                sprite.set_tint_color(ctx.sim.palette_color(self.player_color_index));
             }
         }
 
-        if self.input.is_just_pressed(KeyCode::KeyN) {
+        if ctx.actions.is_action_just_pressed(ctx.input, "background") {
+            self.background_index = (self.background_index + 1) % BACKGROUND_COLORS.len();
+            *ctx.clear_color = BACKGROUND_COLORS[self.background_index];
+        }
+
+        if ctx.actions.is_action_just_pressed(ctx.input, "spawn") {
             let grid_x = (self.spawn_counter % 6) as f32;
             let grid_y = (self.spawn_counter / 6) as f32;
             let position = Vec2::new(grid_x * 110.0 - 220.0, grid_y * 110.0 - 160.0);
-            let tile_index = self.spawn_counter % self.assets.atlas.tile_count().max(1);
-            let color = PALETTE[self.spawn_counter as usize % PALETTE.len()];
-            let spin = if self.spawn_counter % 2 == 0 { 0.4 } else { -0.3 };
-            let angle = self.spawn_counter as f32 * 0.7;
+            let tile_index = self.spawn_counter % ctx.assets.atlas(0).tile_count().max(1);
+            let color = ctx.sim.palette_color(self.spawn_counter as usize);
+            let spin = if self.spawn_counter.is_multiple_of(2) {
+                0.4
+            } else {
+                -0.3
+            };
+            let angle = ctx.rng.next_range(0.0, std::f32::consts::TAU);
             let velocity = Vec2::new(angle.cos(), angle.sin()) * 120.0;
-            self.world.spawn_sprite_with_body(
+            ctx.world.spawn_sprite_with_body(
                 Transform::new(position),
                 Sprite {
-                    size: Vec2::splat(SPRITE_SIZE * 0.6),
-                    tile_index,
-                    color,
                     spin,
-                    animation: None,
+                    fade: Some(Fade::new(0.0, color.a, 0.4)),
+                    ..Sprite::new(Vec2::splat(ctx.sim.sprite_size * 0.6), tile_index, color)
                 },
                 Some(Body::new(velocity)),
             );
             self.spawn_counter = self.spawn_counter.wrapping_add(1);
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{visible_world_rect, world_to_screen};
+
+    #[test]
+    fn camera_is_clamped_into_bounds() {
+        let mut camera = Camera::new();
+        camera.bounds = Some((Vec2::new(-10.0, -5.0), Vec2::new(10.0, 5.0)));
+        camera.position = Vec2::new(25.0, -8.0);
+        camera.clamp_to_bounds();
+        assert_eq!(camera.position, Vec2::new(10.0, -5.0));
+        camera.position = Vec2::new(3.0, 2.0);
+        camera.clamp_to_bounds();
+        assert_eq!(camera.position, Vec2::new(3.0, 2.0));
+        camera.bounds = None;
+        camera.position = Vec2::splat(100.0);
+        camera.clamp_to_bounds();
+        assert_eq!(camera.position, Vec2::splat(100.0));
+    }
+
+    #[test]
+    fn sprites_outside_the_view_are_culled() {
+        let mut world = World::new();
+        for position in [
+            Vec2::ZERO,
+            Vec2::new(104.0, 0.0),
+            Vec2::new(106.0, 0.0),
+            Vec2::new(0.0, -200.0),
+            Vec2::new(-300.0, 300.0),
+        ] {
+            world.spawn_sprite(
+                Transform::new(position),
+                Sprite::new(Vec2::splat(10.0), 0, [1.0; 4]),
+            );
+        }
+        let size = winit::dpi::PhysicalSize::new(200, 100);
+        let view = visible_world_rect(size, Vec2::ZERO, 1.0, 0.0);
+        assert_eq!(view, (Vec2::new(-100.0, -50.0), Vec2::new(100.0, 50.0)));
+
+        let mut culled = 0;
+        world.for_each_sprite_world(1.0, |_, transform, sprite| {
+            if !overlaps_view(sprite.world_aabb(transform), view) {
+                culled += 1;
+            }
+        });
+        assert_eq!(culled, 3);
+    }
+
+    #[test]
+    fn zoom_stays_within_the_limits() {
+        let mut camera = Camera::new();
+        camera.set_zoom_limits(0.5, 2.0);
+        camera.set_zoom(8.0);
+        assert_eq!(camera.zoom, 2.0);
+        camera.zoom_around(0.1, Vec2::new(10.0, 10.0));
+        assert_eq!(camera.zoom, 0.5);
+
+        camera.set_zoom_limits(3.0, 1.0);
+        camera.set_zoom(2.5);
+        assert_eq!(camera.zoom, 2.0);
+        camera.set_zoom_limits(0.5, 1.0);
+        assert_eq!(camera.zoom, 1.0);
+    }
+
+    #[test]
+    fn zoom_around_keeps_the_anchor_under_the_cursor() {
+        let size = winit::dpi::PhysicalSize::new(800, 600);
+        let cursor = Vec2::new(600.0, 150.0);
+        let (position, zoom) = (Vec2::new(30.0, -20.0), 1.5);
+        let anchor = screen_to_world(size, position, zoom, cursor);
 
-        self.world.step_physics(dt, WORLD_BOUNDS);
-        self.world.update_animations(dt);
+        let new_zoom = zoom * ZOOM_PER_SCROLL_LINE.powf(3.0);
+        let moved = zoom_around_position(position, zoom, new_zoom, anchor);
+        let screen = world_to_screen(size, moved, new_zoom, anchor);
+        assert!((screen - cursor).length() < 1e-3);
+        assert_ne!(moved, position);
     }
 }
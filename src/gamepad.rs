@@ -0,0 +1,95 @@
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+use crate::input::{GamepadAxis, GamepadButton, InputState};
+
+// Pumps gilrs events into `InputState`; call `poll` once per frame.
+pub struct GamepadPoller {
+    gilrs: Gilrs,
+}
+
+impl GamepadPoller {
+    // Returns None when the platform gamepad backend cannot start.
+    pub fn new() -> Option<Self> {
+        match Gilrs::new() {
+            Ok(gilrs) => {
+                for (_, gamepad) in gilrs.gamepads() {
+                    log::info!("Gamepad connected: {}", gamepad.name());
+                }
+                Some(Self { gilrs })
+            }
+            Err(err) => {
+                log::warn!("Gamepad support unavailable: {}", err);
+                None
+            }
+        }
+    }
+
+    pub fn poll(&mut self, input: &mut InputState) {
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = map_button(button) {
+                        input.set_gamepad_button(button, true);
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = map_button(button) {
+                        input.set_gamepad_button(button, false);
+                    }
+                }
+                EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+                    input.set_gamepad_axis(GamepadAxis::LeftTrigger, value);
+                }
+                EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+                    input.set_gamepad_axis(GamepadAxis::RightTrigger, value);
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    if let Some(axis) = map_axis(axis) {
+                        input.set_gamepad_axis(axis, value);
+                    }
+                }
+                EventType::Connected => {
+                    log::info!("Gamepad connected: {}", self.gilrs.gamepad(event.id).name());
+                }
+                EventType::Disconnected => {
+                    log::info!("Gamepad disconnected");
+                    if self.gilrs.gamepads().next().is_none() {
+                        input.reset_gamepad();
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.gilrs.inc();
+    }
+}
+
+fn map_button(button: Button) -> Option<GamepadButton> {
+    Some(match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::North => GamepadButton::North,
+        Button::West => GamepadButton::West,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::LeftThumb => GamepadButton::LeftStick,
+        Button::RightThumb => GamepadButton::RightStick,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+fn map_axis(axis: Axis) -> Option<GamepadAxis> {
+    Some(match axis {
+        Axis::LeftStickX => GamepadAxis::LeftStickX,
+        Axis::LeftStickY => GamepadAxis::LeftStickY,
+        Axis::RightStickX => GamepadAxis::RightStickX,
+        Axis::RightStickY => GamepadAxis::RightStickY,
+        _ => return None,
+    })
+}
@@ -1,10 +1,87 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use glam::Vec2;
+use serde::Deserialize;
+use winit::event::MouseButton;
 use winit::keyboard::KeyCode;
 
+const DEFAULT_GAMEPAD_DEADZONE: f32 = 0.15;
+
+// Layout-neutral names: South is A on Xbox, Cross on PlayStation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+// Sticks report -1..1 with +Y up; triggers report 0..1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GamepadStick {
+    Left,
+    Right,
+}
+
+#[derive(Default)]
+struct StepEdges {
+    keys_pressed: HashSet<KeyCode>,
+    keys_released: HashSet<KeyCode>,
+    mouse_pressed: HashSet<MouseButton>,
+    mouse_released: HashSet<MouseButton>,
+    gamepad_pressed: HashSet<GamepadButton>,
+    gamepad_released: HashSet<GamepadButton>,
+}
+
+impl StepEdges {
+    fn clear(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.mouse_pressed.clear();
+        self.mouse_released.clear();
+        self.gamepad_pressed.clear();
+        self.gamepad_released.clear();
+    }
+}
+
 pub struct InputState {
     pressed: HashSet<KeyCode>,
     just_pressed: HashSet<KeyCode>,
     just_released: HashSet<KeyCode>,
+    mouse_pressed: HashSet<MouseButton>,
+    mouse_just_pressed: HashSet<MouseButton>,
+    mouse_just_released: HashSet<MouseButton>,
+    cursor_position: Vec2,
+    scroll_delta: f32,
+    gamepad_pressed: HashSet<GamepadButton>,
+    gamepad_just_pressed: HashSet<GamepadButton>,
+    gamepad_just_released: HashSet<GamepadButton>,
+    gamepad_axes: HashMap<GamepadAxis, f32>,
+    gamepad_deadzone: f32,
+    recorder: Option<InputRecorder>,
+    step_edges: StepEdges,
+    in_fixed_step: bool,
 }
 
 impl InputState {
@@ -13,29 +90,470 @@ impl InputState {
             pressed: HashSet::new(),
             just_pressed: HashSet::new(),
             just_released: HashSet::new(),
+            mouse_pressed: HashSet::new(),
+            mouse_just_pressed: HashSet::new(),
+            mouse_just_released: HashSet::new(),
+            cursor_position: Vec2::ZERO,
+            scroll_delta: 0.0,
+            gamepad_pressed: HashSet::new(),
+            gamepad_just_pressed: HashSet::new(),
+            gamepad_just_released: HashSet::new(),
+            gamepad_axes: HashMap::new(),
+            gamepad_deadzone: DEFAULT_GAMEPAD_DEADZONE,
+            recorder: None,
+            step_edges: StepEdges::default(),
+            in_fixed_step: false,
         }
     }
 
     pub fn set_key(&mut self, key: KeyCode, pressed: bool) {
-        if pressed {
-            if self.pressed.insert(key) {
+        let changed = if pressed {
+            let changed = self.pressed.insert(key);
+            if changed {
                 self.just_pressed.insert(key);
+                self.step_edges.keys_pressed.insert(key);
+            }
+            changed
+        } else {
+            let changed = self.pressed.remove(&key);
+            if changed {
+                self.just_released.insert(key);
+                self.step_edges.keys_released.insert(key);
             }
-        } else if self.pressed.remove(&key) {
-            self.just_released.insert(key);
+            changed
+        };
+        if changed && let Some(recorder) = &mut self.recorder {
+            recorder.record(key, pressed);
         }
     }
 
+    // Restarts recording from frame 0, discarding any recording in progress.
+    pub fn start_recording(&mut self) {
+        self.recorder = Some(InputRecorder::new());
+    }
+
+    pub fn stop_recording(&mut self) -> Option<InputRecorder> {
+        self.recorder.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
     pub fn is_pressed(&self, key: KeyCode) -> bool {
         self.pressed.contains(&key)
     }
 
     pub fn is_just_pressed(&self, key: KeyCode) -> bool {
-        self.just_pressed.contains(&key)
+        if self.in_fixed_step {
+            self.step_edges.keys_pressed.contains(&key)
+        } else {
+            self.just_pressed.contains(&key)
+        }
+    }
+
+    pub fn is_just_released(&self, key: KeyCode) -> bool {
+        if self.in_fixed_step {
+            self.step_edges.keys_released.contains(&key)
+        } else {
+            self.just_released.contains(&key)
+        }
+    }
+
+    // Just-pressed and just-released report each edge to exactly one fixed step.
+    pub fn begin_fixed_step(&mut self) {
+        self.in_fixed_step = true;
+    }
+
+    pub fn end_fixed_step(&mut self) {
+        self.in_fixed_step = false;
+        self.step_edges.clear();
+    }
+
+    pub fn set_mouse_button(&mut self, button: MouseButton, pressed: bool) {
+        if pressed {
+            if self.mouse_pressed.insert(button) {
+                self.mouse_just_pressed.insert(button);
+                self.step_edges.mouse_pressed.insert(button);
+            }
+        } else if self.mouse_pressed.remove(&button) {
+            self.mouse_just_released.insert(button);
+            self.step_edges.mouse_released.insert(button);
+        }
+    }
+
+    pub fn is_mouse_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_pressed.contains(&button)
+    }
+
+    pub fn is_mouse_just_pressed(&self, button: MouseButton) -> bool {
+        if self.in_fixed_step {
+            self.step_edges.mouse_pressed.contains(&button)
+        } else {
+            self.mouse_just_pressed.contains(&button)
+        }
+    }
+
+    pub fn is_mouse_just_released(&self, button: MouseButton) -> bool {
+        if self.in_fixed_step {
+            self.step_edges.mouse_released.contains(&button)
+        } else {
+            self.mouse_just_released.contains(&button)
+        }
+    }
+
+    // Cursor position in physical pixels, origin at the window's top-left.
+    pub fn set_cursor_position(&mut self, position: Vec2) {
+        self.cursor_position = position;
+    }
+
+    pub fn cursor_position(&self) -> Vec2 {
+        self.cursor_position
+    }
+
+    // Lines, positive away from the user, accumulated until the end of the frame.
+    pub fn add_scroll(&mut self, lines: f32) {
+        self.scroll_delta += lines;
+    }
+
+    pub fn scroll_delta(&self) -> f32 {
+        self.scroll_delta
+    }
+
+    pub fn set_gamepad_button(&mut self, button: GamepadButton, pressed: bool) {
+        if pressed {
+            if self.gamepad_pressed.insert(button) {
+                self.gamepad_just_pressed.insert(button);
+                self.step_edges.gamepad_pressed.insert(button);
+            }
+        } else if self.gamepad_pressed.remove(&button) {
+            self.gamepad_just_released.insert(button);
+            self.step_edges.gamepad_released.insert(button);
+        }
+    }
+
+    pub fn gamepad_button(&self, button: GamepadButton) -> bool {
+        self.gamepad_pressed.contains(&button)
+    }
+
+    pub fn is_gamepad_just_pressed(&self, button: GamepadButton) -> bool {
+        if self.in_fixed_step {
+            self.step_edges.gamepad_pressed.contains(&button)
+        } else {
+            self.gamepad_just_pressed.contains(&button)
+        }
+    }
+
+    pub fn is_gamepad_just_released(&self, button: GamepadButton) -> bool {
+        if self.in_fixed_step {
+            self.step_edges.gamepad_released.contains(&button)
+        } else {
+            self.gamepad_just_released.contains(&button)
+        }
+    }
+
+    // Stores the raw value; the deadzone is applied when reading.
+    pub fn set_gamepad_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.gamepad_axes.insert(axis, value.clamp(-1.0, 1.0));
+    }
+
+    // Releases every button and centres every axis, e.g. on disconnect.
+    pub fn reset_gamepad(&mut self) {
+        for button in self.gamepad_pressed.drain() {
+            self.gamepad_just_released.insert(button);
+            self.step_edges.gamepad_released.insert(button);
+        }
+        self.gamepad_axes.clear();
+    }
+
+    // Zero inside the deadzone, rescaled to reach 1 at full deflection.
+    pub fn gamepad_axis(&self, axis: GamepadAxis) -> f32 {
+        let value = self.gamepad_axes.get(&axis).copied().unwrap_or(0.0);
+        let magnitude = rescale_past_deadzone(value.abs(), self.gamepad_deadzone);
+        magnitude.copysign(value)
+    }
+
+    // Both axes of a stick with a radial deadzone.
+    pub fn gamepad_stick(&self, stick: GamepadStick) -> Vec2 {
+        let (x_axis, y_axis) = match stick {
+            GamepadStick::Left => (GamepadAxis::LeftStickX, GamepadAxis::LeftStickY),
+            GamepadStick::Right => (GamepadAxis::RightStickX, GamepadAxis::RightStickY),
+        };
+        let raw = Vec2::new(
+            self.gamepad_axes.get(&x_axis).copied().unwrap_or(0.0),
+            self.gamepad_axes.get(&y_axis).copied().unwrap_or(0.0),
+        );
+        let length = raw.length();
+        if length <= self.gamepad_deadzone {
+            return Vec2::ZERO;
+        }
+        raw / length * rescale_past_deadzone(length.min(1.0), self.gamepad_deadzone)
+    }
+
+    pub fn gamepad_deadzone(&self) -> f32 {
+        self.gamepad_deadzone
+    }
+
+    pub fn set_gamepad_deadzone(&mut self, deadzone: f32) {
+        self.gamepad_deadzone = deadzone.clamp(0.0, 0.99);
     }
 
+    // Clears the per-frame edges and scroll.
     pub fn finish_frame(&mut self) {
         self.just_pressed.clear();
         self.just_released.clear();
+        self.mouse_just_pressed.clear();
+        self.mouse_just_released.clear();
+        self.gamepad_just_pressed.clear();
+        self.gamepad_just_released.clear();
+        self.scroll_delta = 0.0;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.frame += 1;
+        }
+    }
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Maps action names to one or more keys.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct ActionMap {
+    bindings: HashMap<String, Vec<KeyCode>>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn bind(&mut self, action: &str, key: KeyCode) {
+        let keys = self.bindings.entry(action.to_string()).or_default();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    pub fn unbind(&mut self, action: &str) {
+        self.bindings.remove(action);
+    }
+
+    pub fn keys(&self, action: &str) -> &[KeyCode] {
+        self.bindings.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_action_pressed(&self, input: &InputState, action: &str) -> bool {
+        self.keys(action).iter().any(|key| input.is_pressed(*key))
+    }
+
+    pub fn is_action_just_pressed(&self, input: &InputState, action: &str) -> bool {
+        self.keys(action)
+            .iter()
+            .any(|key| input.is_just_pressed(*key))
+    }
+
+    pub fn is_action_just_released(&self, input: &InputState, action: &str) -> bool {
+        self.keys(action)
+            .iter()
+            .any(|key| input.is_just_released(*key))
+    }
+}
+
+// `frame` counts `InputState::finish_frame` calls since recording started.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: u64,
+    pub key: KeyCode,
+    pub pressed: bool,
+}
+
+// Key changes captured from `InputState::set_key`, one per line as text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputRecorder {
+    frame: u64,
+    events: Vec<KeyEvent>,
+}
+
+impl InputRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&mut self, key: KeyCode, pressed: bool) {
+        self.events.push(KeyEvent {
+            frame: self.frame,
+            key,
+            pressed,
+        });
+    }
+
+    pub fn events(&self) -> &[KeyEvent] {
+        &self.events
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for event in &self.events {
+            let key = serde_json::to_string(&event.key).unwrap_or_default();
+            let state = if event.pressed { "pressed" } else { "released" };
+            text.push_str(&format!(
+                "{} {} {}\n",
+                event.frame,
+                key.trim_matches('"'),
+                state
+            ));
+        }
+        text
+    }
+
+    // Blank lines are skipped; anything else malformed is an InvalidData error.
+    pub fn from_text(text: &str) -> std::io::Result<Self> {
+        let mut events = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = parse_key_event(line).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid input event on line {}: {}", number + 1, line),
+                )
+            })?;
+            events.push(event);
+        }
+        let frame = events.last().map_or(0, |event| event.frame);
+        Ok(Self { frame, events })
+    }
+
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        Self::from_text(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+}
+
+fn parse_key_event(line: &str) -> Option<KeyEvent> {
+    let mut parts = line.split_whitespace();
+    let frame = parts.next()?.parse().ok()?;
+    let key = serde_json::from_str(&format!("\"{}\"", parts.next()?)).ok()?;
+    let pressed = match parts.next()? {
+        "pressed" => true,
+        "released" => false,
+        _ => return None,
+    };
+    parts.next().is_none().then_some(KeyEvent {
+        frame,
+        key,
+        pressed,
+    })
+}
+
+// Feeds a recording back into an `InputState`, one frame per `apply_frame`.
+pub struct InputPlayback {
+    events: Vec<KeyEvent>,
+    next: usize,
+    frame: u64,
+}
+
+impl InputPlayback {
+    pub fn new(recording: InputRecorder) -> Self {
+        Self {
+            events: recording.events,
+            next: 0,
+            frame: 0,
+        }
+    }
+
+    // Call once per frame in place of the window's key events.
+    pub fn apply_frame(&mut self, input: &mut InputState) {
+        while let Some(event) = self.events.get(self.next) {
+            if event.frame > self.frame {
+                break;
+            }
+            input.set_key(event.key, event.pressed);
+            self.next += 1;
+        }
+        self.frame += 1;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.events.len()
+    }
+}
+
+fn rescale_past_deadzone(magnitude: f32, deadzone: f32) -> f32 {
+    if magnitude <= deadzone {
+        0.0
+    } else {
+        ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_replays_to_the_same_state_through_text() {
+        let mut live = InputState::new();
+        live.start_recording();
+        live.set_key(KeyCode::KeyA, true);
+        live.finish_frame();
+        live.set_key(KeyCode::Space, true);
+        live.set_key(KeyCode::KeyA, true);
+        live.finish_frame();
+        live.finish_frame();
+        live.set_key(KeyCode::Space, false);
+        live.set_key(KeyCode::ArrowLeft, true);
+        live.finish_frame();
+        let recording = live.stop_recording().unwrap();
+        assert_eq!(recording.events().len(), 4);
+
+        let text = recording.to_text();
+        assert!(text.starts_with("0 KeyA pressed\n1 Space pressed\n"));
+        let parsed = InputRecorder::from_text(&text).unwrap();
+        assert_eq!(parsed.events(), recording.events());
+
+        let mut replayed = InputState::new();
+        let mut playback = InputPlayback::new(parsed);
+        while !playback.is_finished() {
+            playback.apply_frame(&mut replayed);
+            replayed.finish_frame();
+        }
+        assert_eq!(replayed.pressed, live.pressed);
+        assert!(replayed.is_pressed(KeyCode::KeyA));
+        assert!(replayed.is_pressed(KeyCode::ArrowLeft));
+        assert!(!replayed.is_pressed(KeyCode::Space));
+    }
+
+    #[test]
+    fn tap_reaches_exactly_one_fixed_step() {
+        let mut input = InputState::new();
+        input.set_key(KeyCode::Space, true);
+        input.set_key(KeyCode::Space, false);
+        input.finish_frame();
+
+        let mut seen = Vec::new();
+        for _ in 0..2 {
+            input.begin_fixed_step();
+            seen.push((
+                input.is_just_pressed(KeyCode::Space),
+                input.is_just_released(KeyCode::Space),
+            ));
+            input.end_fixed_step();
+        }
+        input.finish_frame();
+        assert_eq!(seen, vec![(true, true), (false, false)]);
+        assert!(!input.is_pressed(KeyCode::Space));
     }
 }
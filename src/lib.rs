@@ -0,0 +1,18 @@
+pub mod assets;
+pub mod bloom;
+pub mod color;
+pub mod config;
+pub mod debug_draw;
+pub mod engine;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod input;
+pub mod renderer;
+pub mod rng;
+pub mod scene;
+pub mod shapes;
+pub mod text;
+pub mod tilemap;
+pub mod time;
+#[cfg(feature = "notify")]
+pub mod watcher;
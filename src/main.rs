@@ -1,30 +1,30 @@
-mod assets;
-mod engine;
-mod input;
-mod renderer;
-mod scene;
-mod time;
-
 use std::sync::Arc;
 
-use engine::Engine;
+use engine2d::config::EngineConfig;
+use engine2d::engine::Engine;
 use winit::event::{ElementState, Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::keyboard::PhysicalKey;
-use winit::window::WindowBuilder;
 
 fn main() {
     env_logger::init();
 
-    let event_loop = EventLoop::new().expect("create event loop");
-    let window = Arc::new(
-        WindowBuilder::new()
-            .with_title("engine2d")
-            .build(&event_loop)
-            .expect("build window"),
-    );
+    let config = EngineConfig::default();
+    let event_loop = match EventLoop::new() {
+        Ok(event_loop) => event_loop,
+        Err(err) => exit_with_error("Could not start the windowing system", &err),
+    };
+    let window = match config.window_builder().build(&event_loop) {
+        Ok(window) => Arc::new(window),
+        Err(err) => exit_with_error("Could not open a window", &err),
+    };
 
-    let mut engine = pollster::block_on(Engine::new(window.clone()));
+    let mut engine = match pollster::block_on(Engine::new(window.clone(), &config)) {
+        Ok(engine) => engine,
+        Err(err) => exit_with_error("Could not initialise graphics", &err),
+    };
+    let bounds = engine.sim_config().world_bounds;
+    engine.set_camera_bounds(Some((-bounds, bounds)));
 
     event_loop
         .run(move |event, elwt| {
@@ -35,7 +35,8 @@ fn main() {
                     match event {
                         WindowEvent::CloseRequested => elwt.exit(),
                         WindowEvent::Resized(size) => engine.resize(size),
-                        WindowEvent::ScaleFactorChanged { .. } => {
+                        WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                            engine.set_scale_factor(scale_factor);
                             engine.resize(engine.window().inner_size())
                         }
                         WindowEvent::KeyboardInput { event, .. } => {
@@ -44,21 +45,30 @@ fn main() {
                                 engine.handle_key(code, pressed);
                             }
                         }
-                        WindowEvent::RedrawRequested => {
-                            match engine.redraw() {
-                                Ok(()) => {}
-                                Err(wgpu::SurfaceError::Lost) => engine.resize(engine.window().inner_size()),
-                                Err(wgpu::SurfaceError::Outdated) => {}
-                                Err(wgpu::SurfaceError::Timeout) => {
-                                    log::warn!("Surface timeout")
-                                }
-                                Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
-                            }
+                        WindowEvent::MouseInput { state, button, .. } => {
+                            engine.handle_mouse_button(button, state == ElementState::Pressed);
                         }
+                        WindowEvent::CursorMoved { position, .. } => {
+                            engine.handle_cursor_moved(position)
+                        }
+                        WindowEvent::MouseWheel { delta, .. } => engine.handle_mouse_wheel(delta),
+                        WindowEvent::RedrawRequested => match engine.redraw() {
+                            Ok(()) => {}
+                            Err(wgpu::SurfaceError::Lost) => {
+                                engine.resize(engine.window().inner_size())
+                            }
+                            Err(wgpu::SurfaceError::Outdated) => {}
+                            Err(wgpu::SurfaceError::Timeout) => {
+                                log::warn!("Surface timeout")
+                            }
+                            Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
+                        },
                         _ => {}
                     }
                 }
                 Event::AboutToWait => {
+                    #[cfg(feature = "gamepad")]
+                    engine.poll_gamepad();
                     engine.window().request_redraw();
                 }
                 _ => {}
@@ -66,3 +76,8 @@ fn main() {
         })
         .expect("run event loop");
 }
+
+fn exit_with_error(context: &str, err: &dyn std::fmt::Display) -> ! {
+    eprintln!("{}: {}", context, err);
+    std::process::exit(1);
+}
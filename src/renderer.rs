@@ -1,3 +1,6 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
 use std::sync::Arc;
 
 use glam::Vec2;
@@ -5,9 +8,22 @@ use wgpu::util::DeviceExt;
 use winit::window::Window;
 
 use crate::assets::{Atlas, Texture};
-use crate::scene::{Sprite, Transform};
+use crate::bloom::{Bloom, BloomSettings};
+use crate::color::Color;
+use crate::config::EngineConfig;
+use crate::debug_draw::LineVertex;
+use crate::scene::{BlendMode, MaterialId, NineSlice, Sprite, Transform};
+use crate::shapes::ShapeInstance;
+use crate::text::BitmapFont;
 
-const SHADER: &str = r#"
+const INSTANCE_SHRINK_FRAMES: u32 = 120;
+// `InstanceUpload::Auto` switches to the staging path from this many instances.
+pub const STAGING_UPLOAD_MIN_INSTANCES: usize = 10_000;
+const STAGING_CHUNK_SIZE: u64 = 1 << 20;
+pub const MAX_VIEWPORTS: usize = 4;
+const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+const SPRITE_SHADER_PRELUDE: &str = r#"
 struct SceneUniform {
     view_proj: mat4x4<f32>,
 };
@@ -16,7 +32,7 @@ struct SceneUniform {
 var<uniform> scene: SceneUniform;
 
 @group(1) @binding(0)
-var sprite_tex: texture_2d<f32>;
+var sprite_tex: texture_2d_array<f32>;
 
 @group(1) @binding(1)
 var sprite_sampler: sampler;
@@ -30,12 +46,15 @@ struct VertexInput {
     @location(5) uv_max: vec2<f32>,
     @location(6) rotation: f32,
     @location(7) color: vec4<f32>,
+    @location(8) anchor: vec2<f32>,
+    @location(9) array_layer: u32,
 };
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) uv: vec2<f32>,
     @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) array_layer: u32,
 };
 
 @vertex
@@ -43,20 +62,148 @@ fn vs_main(input: VertexInput) -> VertexOutput {
     var out: VertexOutput;
     let c = cos(input.rotation);
     let s = sin(input.rotation);
+    // The anchor point of the quad lands on the instance position and is the
+    // pivot for rotation.
+    let local = (input.position - input.anchor) * input.instance_size;
     let rotated = vec2<f32>(
-        input.position.x * c - input.position.y * s,
-        input.position.x * s + input.position.y * c,
+        local.x * c - local.y * s,
+        local.x * s + local.y * c,
     );
-    let world = rotated * input.instance_size + input.instance_pos;
+    let world = rotated + input.instance_pos;
     out.position = scene.view_proj * vec4<f32>(world, 0.0, 1.0);
     out.uv = input.uv * (input.uv_max - input.uv_min) + input.uv_min;
     out.color = input.color;
+    out.array_layer = input.array_layer;
+    return out;
+}
+"#;
+
+const DEFAULT_MATERIAL: &str = r#"
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = textureSample(sprite_tex, sprite_sampler, input.uv, input.array_layer);
+    return texel * input.color;
+}
+"#;
+
+const LINE_SHADER: &str = r#"
+struct SceneUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> scene: SceneUniform;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = scene.view_proj * vec4<f32>(input.position, 0.0, 1.0);
+    out.color = input.color;
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return input.color;
+}
+"#;
+
+const SHAPE_SHADER: &str = r#"
+struct SceneUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> scene: SceneUniform;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) center: vec2<f32>,
+    @location(3) size: vec2<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) corner_radius: f32,
+    @location(6) softness: f32,
+    @location(7) kind: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) local: vec2<f32>,
+    @location(1) half_size: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) corner_radius: f32,
+    @location(4) softness: f32,
+    @location(5) @interpolate(flat) kind: u32,
+};
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.local = input.position * input.size;
+    out.position = scene.view_proj * vec4<f32>(out.local + input.center, 0.0, 1.0);
+    out.half_size = abs(input.size) * 0.5;
+    out.color = input.color;
+    out.corner_radius = input.corner_radius;
+    out.softness = input.softness;
+    out.kind = input.kind;
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let half_size = input.half_size;
+    var distance: f32;
+    if input.kind == 0u {
+        distance = length(input.local) - min(half_size.x, half_size.y);
+    } else {
+        let radius = clamp(input.corner_radius, 0.0, min(half_size.x, half_size.y));
+        let q = abs(input.local) - half_size + vec2<f32>(radius);
+        distance = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+    }
+    // The edge fades in just inside the quad so it is never clipped.
+    let edge = max(fwidth(distance) * input.softness, 1e-5);
+    let coverage = 1.0 - smoothstep(-edge, 0.0, distance);
+    return vec4<f32>(input.color.rgb, input.color.a * coverage);
+}
+"#;
+
+const BACKGROUND_SHADER: &str = r#"
+struct BackgroundUniform {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> background: BackgroundUniform;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) t: f32,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.t = uv.y;
     return out;
 }
 
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(sprite_tex, sprite_sampler, input.uv) * input.color;
+    return mix(background.top, background.bottom, input.t);
 }
 "#;
 
@@ -89,16 +236,20 @@ pub struct InstanceRaw {
     uv_max: [f32; 2],
     rotation: f32,
     color: [f32; 4],
+    anchor: [f32; 2],
+    array_layer: u32,
 }
 
 impl InstanceRaw {
-    const ATTRIBS: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+    const ATTRIBS: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
         2 => Float32x2,
         3 => Float32x2,
         4 => Float32x2,
         5 => Float32x2,
         6 => Float32,
-        7 => Float32x4
+        7 => Float32x4,
+        8 => Float32x2,
+        9 => Uint32
     ];
 
     fn desc() -> wgpu::VertexBufferLayout<'static> {
@@ -110,7 +261,14 @@ impl InstanceRaw {
     }
 
     pub fn from_components(transform: &Transform, sprite: &Sprite, atlas: &Atlas) -> Self {
-        let (uv_min, uv_max) = atlas.uv_for_index(sprite.tile_index);
+        let (mut uv_min, mut uv_max) = atlas.uv_for_index(sprite.tile_index);
+        if sprite.flip_x {
+            std::mem::swap(&mut uv_min.x, &mut uv_max.x);
+        }
+        if sprite.flip_y {
+            std::mem::swap(&mut uv_min.y, &mut uv_max.y);
+        }
+        let (uv_min, uv_max) = (uv_min + sprite.uv_offset, uv_max + sprite.uv_offset);
         let size = sprite.size * transform.scale;
         Self {
             position: [transform.position.x, transform.position.y],
@@ -118,11 +276,136 @@ impl InstanceRaw {
             uv_min: [uv_min.x, uv_min.y],
             uv_max: [uv_max.x, uv_max.y],
             rotation: transform.rotation,
-            color: sprite.color,
+            color: sprite.color.to_linear(),
+            anchor: [sprite.anchor.x, sprite.anchor.y],
+            array_layer: sprite.array_layer,
         }
     }
+
+    // Splits a sprite into its nine-slice pieces, row by row from the top-left.
+    pub fn nine_slice(
+        transform: &Transform,
+        sprite: &Sprite,
+        atlas: &Atlas,
+        slice: NineSlice,
+    ) -> [Self; 9] {
+        let base = Self::from_components(transform, sprite, atlas);
+        let (uv_min, uv_max) = atlas.uv_for_index(sprite.tile_index);
+        let texels = atlas.tile_pixel_size(sprite.tile_index).max(Vec2::ONE);
+        let uv_per_texel = (uv_max - uv_min) / texels;
+        let [left, top, right, bottom] = slice.border.map(|border| border.max(0.0));
+        let size = sprite.size.abs();
+        let fit = |room: Vec2| {
+            Vec2::new(
+                (room.x / (left + right)).min(1.0),
+                (room.y / (top + bottom)).min(1.0),
+            )
+        };
+        let Vec2 { x: fit_x, y: fit_y } = fit(size);
+        let uv_fit = fit(texels) * uv_per_texel;
+        let xs = [0.0, left * fit_x, size.x - right * fit_x, size.x];
+        let ys = [0.0, top * fit_y, size.y - bottom * fit_y, size.y];
+        let us = [
+            uv_min.x,
+            uv_min.x + left * uv_fit.x,
+            uv_max.x - right * uv_fit.x,
+            uv_max.x,
+        ];
+        let vs = [
+            uv_min.y,
+            uv_min.y + top * uv_fit.y,
+            uv_max.y - bottom * uv_fit.y,
+            uv_max.y,
+        ];
+        let sign = sprite.size.signum();
+        let rotation = Vec2::from_angle(transform.rotation);
+        std::array::from_fn(|piece| {
+            let (row, column) = (piece / 3, piece % 3);
+            let mut center = Vec2::new(
+                (xs[column] + xs[column + 1]) * 0.5 - size.x * 0.5,
+                size.y * 0.5 - (ys[row] + ys[row + 1]) * 0.5,
+            ) * sign;
+            let mut piece_uv_min = Vec2::new(us[column], vs[row]);
+            let mut piece_uv_max = Vec2::new(us[column + 1], vs[row + 1]);
+            if sprite.flip_x {
+                center.x = -center.x;
+                std::mem::swap(&mut piece_uv_min.x, &mut piece_uv_max.x);
+            }
+            if sprite.flip_y {
+                center.y = -center.y;
+                std::mem::swap(&mut piece_uv_min.y, &mut piece_uv_max.y);
+            }
+            let offset = rotation.rotate((center - sprite.anchor * sprite.size) * transform.scale);
+            let position = transform.position + offset;
+            let piece_size = Vec2::new(xs[column + 1] - xs[column], ys[row + 1] - ys[row])
+                * sign
+                * transform.scale;
+            Self {
+                position: [position.x, position.y],
+                size: [piece_size.x, piece_size.y],
+                uv_min: [piece_uv_min.x, piece_uv_min.y],
+                uv_max: [piece_uv_max.x, piece_uv_max.y],
+                anchor: [0.0, 0.0],
+                ..base
+            }
+        })
+    }
+}
+
+// A run of instances drawn with one texture binding and one pipeline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawBatch {
+    pub texture_id: u32,
+    pub blend: BlendMode,
+    pub material: MaterialId,
+    pub instances: Range<u32>,
+}
+
+// Merges neighbouring instances that share a texture, blend mode and material.
+pub fn build_batches(
+    keys: impl IntoIterator<Item = (u32, BlendMode, MaterialId)>,
+) -> Vec<DrawBatch> {
+    let mut batches: Vec<DrawBatch> = Vec::new();
+    for (index, (texture_id, blend, material)) in keys.into_iter().enumerate() {
+        let index = index as u32;
+        match batches.last_mut() {
+            Some(batch)
+                if batch.texture_id == texture_id
+                    && batch.blend == blend
+                    && batch.material == material =>
+            {
+                batch.instances.end = index + 1
+            }
+            _ => batches.push(DrawBatch {
+                texture_id,
+                blend,
+                material,
+                instances: index..index + 1,
+            }),
+        }
+    }
+    batches
+}
+
+// Work done for one frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    // Quads drawn: sprites, shapes and text glyphs.
+    pub instances: u32,
+    pub draw_calls: u32,
+    // Bytes uploaded through `write_buffer` or the staging belt.
+    pub bytes_uploaded: u64,
 }
 
+const ADDITIVE_BLENDING: wgpu::BlendState = wgpu::BlendState {
+    color: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::SrcAlpha,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    },
+    alpha: wgpu::BlendComponent::OVER,
+};
+
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct SceneUniform {
@@ -130,79 +413,274 @@ struct SceneUniform {
 }
 
 impl SceneUniform {
-    fn new() -> Self {
+    fn new(view_proj: glam::Mat4) -> Self {
+        Self {
+            view_proj: view_proj.to_cols_array_2d(),
+        }
+    }
+}
+
+// A second camera drawn into part of the target, e.g. for split-screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub rect: [f32; 4],
+    pub position: Vec2,
+    pub zoom: f32,
+    pub roll: f32,
+}
+
+impl Viewport {
+    pub fn new(rect: [f32; 4], position: Vec2, zoom: f32) -> Self {
         Self {
-            view_proj: glam::Mat4::IDENTITY.to_cols_array_2d(),
+            rect,
+            position,
+            zoom,
+            roll: 0.0,
+        }
+    }
+
+    // [x, y, width, height] in target pixels, clipped to the target.
+    pub fn pixel_rect(&self, size: winit::dpi::PhysicalSize<u32>) -> [f32; 4] {
+        let target = Vec2::new(size.width.max(1) as f32, size.height.max(1) as f32);
+        let [x, y, width, height] = self.rect;
+        let min = (Vec2::new(x, y) * target).clamp(Vec2::ZERO, target).round();
+        let max = (Vec2::new(x + width, y + height) * target)
+            .clamp(Vec2::ZERO, target)
+            .round();
+        [min.x, min.y, max.x - min.x, max.y - min.y]
+    }
+
+    fn pixel_size(&self, size: winit::dpi::PhysicalSize<u32>) -> winit::dpi::PhysicalSize<u32> {
+        let [_, _, width, height] = self.pixel_rect(size);
+        winit::dpi::PhysicalSize::new(width as u32, height as u32)
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BackgroundUniform {
+    top: [f32; 4],
+    bottom: [f32; 4],
+}
+
+#[derive(Debug)]
+pub enum RendererError {
+    SurfaceCreation(wgpu::CreateSurfaceError),
+    NoAdapter,
+    NoDevice(wgpu::RequestDeviceError),
+    // The adapter cannot present to the window's surface in any format.
+    UnsupportedSurface,
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::SurfaceCreation(err) => {
+                write!(
+                    f,
+                    "could not create a drawing surface for the window: {}",
+                    err
+                )
+            }
+            RendererError::NoAdapter => write!(
+                f,
+                "no compatible graphics adapter found; a GPU with Vulkan, Metal or DirectX 12 \
+                 support and up-to-date drivers is required"
+            ),
+            RendererError::NoDevice(err) => {
+                write!(f, "the graphics adapter could not open a device: {}", err)
+            }
+            RendererError::UnsupportedSurface => {
+                write!(f, "the graphics adapter cannot present to this window")
+            }
+        }
+    }
+}
+
+// How `update_instances` gets instance data to the GPU.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InstanceUpload {
+    #[default]
+    Auto,
+    WriteBuffer,
+    Staging,
+}
+
+impl InstanceUpload {
+    fn uses_staging(self, instance_count: usize) -> bool {
+        match self {
+            InstanceUpload::Auto => instance_count >= STAGING_UPLOAD_MIN_INSTANCES,
+            InstanceUpload::WriteBuffer => false,
+            InstanceUpload::Staging => true,
+        }
+    }
+}
+
+// Why `register_material` rejected a fragment shader.
+#[derive(Debug)]
+pub enum MaterialError {
+    // The message comes from wgpu's validation of the combined shader.
+    Compile(String),
+}
+
+impl fmt::Display for MaterialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialError::Compile(message) => {
+                write!(f, "the material shader failed to compile: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MaterialError {}
+
+// Why `render_to_texture` or `capture_screenshot` produced no pixels.
+#[derive(Debug)]
+pub enum CaptureError {
+    // The format cannot be copied, or is not 4 bytes per pixel for screenshots.
+    UnsupportedFormat(wgpu::TextureFormat),
+    // The readback buffer could not be mapped, e.g. after a device loss.
+    Map(wgpu::BufferAsyncError),
+    Save(image::ImageError),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::UnsupportedFormat(format) => {
+                write!(f, "pixels cannot be read back from a {:?} target", format)
+            }
+            CaptureError::Map(err) => write!(f, "could not read the frame back: {}", err),
+            CaptureError::Save(err) => write!(f, "could not save the image: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CaptureError::UnsupportedFormat(_) => None,
+            CaptureError::Map(err) => Some(err),
+            CaptureError::Save(err) => Some(err),
         }
     }
+}
+
+struct MaterialPipelines {
+    alpha: wgpu::RenderPipeline,
+    additive: wgpu::RenderPipeline,
+    wireframe: Option<wgpu::RenderPipeline>,
+}
 
-    fn update(&mut self, view_proj: glam::Mat4) {
-        self.view_proj = view_proj.to_cols_array_2d();
+impl std::error::Error for RendererError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RendererError::SurfaceCreation(err) => Some(err),
+            RendererError::NoDevice(err) => Some(err),
+            RendererError::NoAdapter | RendererError::UnsupportedSurface => None,
+        }
     }
 }
 
 pub struct Renderer {
-    window: Arc<Window>,
-    surface: wgpu::Surface<'static>,
+    surface: Option<wgpu::Surface<'static>>,
+    headless_target: Option<wgpu::Texture>,
     device: wgpu::Device,
     queue: wgpu::Queue,
+    adapter_info: wgpu::AdapterInfo,
+    limits: wgpu::Limits,
     config: wgpu::SurfaceConfiguration,
+    supported_present_modes: Vec<wgpu::PresentMode>,
     size: winit::dpi::PhysicalSize<u32>,
+    sample_count: u32,
+    msaa_view: Option<wgpu::TextureView>,
     clear_color: wgpu::Color,
-    render_pipeline: wgpu::RenderPipeline,
+    background_gradient: Option<BackgroundUniform>,
+    background_pipeline: wgpu::RenderPipeline,
+    background_buffer: wgpu::Buffer,
+    background_bind_group: wgpu::BindGroup,
+    sprite_pipeline_layout: wgpu::PipelineLayout,
+    materials: Vec<MaterialPipelines>,
+    wireframe: bool,
+    bloom: Option<Bloom>,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     num_indices: u32,
     instance_buffer: wgpu::Buffer,
     instance_capacity: usize,
-    num_instances: u32,
-    scene_uniform: SceneUniform,
+    instance_low_frames: u32,
+    instance_upload: InstanceUpload,
+    staging_belt: wgpu::util::StagingBelt,
+    pending_upload: Option<wgpu::CommandBuffer>,
+    batches: Vec<DrawBatch>,
+    line_pipeline: wgpu::RenderPipeline,
+    line_buffer: wgpu::Buffer,
+    line_capacity: usize,
+    line_vertex_count: u32,
+    shape_pipeline: wgpu::RenderPipeline,
+    shape_buffer: wgpu::Buffer,
+    shape_capacity: usize,
+    shape_count: u32,
+    font: BitmapFont,
+    font_bind_group: wgpu::BindGroup,
+    text_pipeline: wgpu::RenderPipeline,
+    text_instances: Vec<InstanceRaw>,
+    text_buffer: wgpu::Buffer,
+    text_capacity: usize,
+    camera_position: Vec2,
+    camera_zoom: f32,
+    scale_factor: f32,
+    camera_roll: f32,
+    pixel_snap: bool,
+    stats: RenderStats,
+    frame_stats: RenderStats,
     scene_buffer: wgpu::Buffer,
+    scene_uniform_stride: u64,
+    viewports: Vec<Viewport>,
     scene_bind_group: wgpu::BindGroup,
     texture_bind_group_layout: wgpu::BindGroupLayout,
-    texture_bind_group: Option<wgpu::BindGroup>,
+    texture_bind_groups: Vec<wgpu::BindGroup>,
 }
 
 impl Renderer {
-    pub async fn new(window: Arc<Window>) -> Self {
+    pub async fn new(
+        window: Arc<Window>,
+        engine_config: &EngineConfig,
+    ) -> Result<Self, RendererError> {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
             ..Default::default()
         });
-        let surface = instance.create_surface(window.clone()).expect("create surface");
+        let surface = instance
+            .create_surface(window.clone())
+            .map_err(RendererError::SurfaceCreation)?;
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .expect("request adapter");
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                },
-                None,
-            )
-            .await
-            .expect("request device");
+        let (adapter, device, queue) =
+            request_device(&instance, Some(&surface), engine_config).await?;
 
         let surface_caps = surface.get_capabilities(&adapter);
+        let Some(&fallback_format) = surface_caps.formats.first() else {
+            return Err(RendererError::UnsupportedSurface);
+        };
         let surface_format = surface_caps
             .formats
             .iter()
             .copied()
             .find(|format| format.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
-        let present_mode = surface_caps.present_modes[0];
-        let alpha_mode = surface_caps.alpha_modes[0];
+            .unwrap_or(fallback_format);
+        let supported_present_modes = surface_caps.present_modes.clone();
+        let present_mode = select_present_mode(
+            engine_config.requested_present_mode(),
+            &supported_present_modes,
+        );
+        let alpha_mode = surface_caps
+            .alpha_modes
+            .first()
+            .copied()
+            .unwrap_or(wgpu::CompositeAlphaMode::Auto);
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -216,9 +694,73 @@ impl Renderer {
         };
         surface.configure(&device, &config);
 
+        let sample_count = select_sample_count(
+            engine_config.msaa_samples,
+            adapter.get_texture_format_features(surface_format).flags,
+        );
+        let mut renderer = Self::from_parts(
+            device,
+            queue,
+            adapter.get_info(),
+            config,
+            sample_count,
+            engine_config.clear_color,
+        );
+        renderer.scale_factor = window.scale_factor() as f32;
+        renderer.surface = Some(surface);
+        renderer.supported_present_modes = supported_present_modes;
+        renderer.instance_upload = engine_config.instance_upload;
+        renderer.set_bloom(engine_config.bloom);
+        if let Some([top, bottom]) = engine_config.background_gradient {
+            renderer.set_background_gradient(top, bottom);
+        }
+        Ok(renderer)
+    }
+
+    // Renders into an owned `Rgba8UnormSrgb` texture instead of a window.
+    pub async fn new_headless(width: u32, height: u32) -> Result<Self, RendererError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY,
+            ..Default::default()
+        });
+        let engine_config = EngineConfig::default();
+        let (adapter, device, queue) = request_device(&instance, None, &engine_config).await?;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: HEADLESS_FORMAT,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let mut renderer = Self::from_parts(
+            device,
+            queue,
+            adapter.get_info(),
+            config,
+            1,
+            engine_config.clear_color,
+        );
+        renderer.headless_target = Some(create_headless_target(&renderer.device, renderer.size));
+        Ok(renderer)
+    }
+
+    fn from_parts(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        adapter_info: wgpu::AdapterInfo,
+        config: wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        clear_color: [f32; 4],
+    ) -> Self {
+        let size = winit::dpi::PhysicalSize::new(config.width, config.height);
+        let msaa_view = create_msaa_view(&device, config.format, size, sample_count);
+
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Sprite Shader"),
-            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+            source: wgpu::ShaderSource::Wgsl(material_source(DEFAULT_MATERIAL).into()),
         });
 
         let scene_bind_group_layout =
@@ -229,8 +771,10 @@ impl Renderer {
                     visibility: wgpu::ShaderStages::VERTEX,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<SceneUniform>() as u64,
+                        ),
                     },
                     count: None,
                 }],
@@ -245,7 +789,7 @@ impl Renderer {
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
                             sample_type: wgpu::TextureSampleType::Float { filterable: true },
                         },
                         count: None,
@@ -259,23 +803,51 @@ impl Renderer {
                 ],
             });
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Sprite Pipeline Layout"),
-            bind_group_layouts: &[&scene_bind_group_layout, &texture_bind_group_layout],
+        let sprite_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Sprite Pipeline Layout"),
+                bind_group_layouts: &[&scene_bind_group_layout, &texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let default_material = create_material_pipelines(
+            &device,
+            &sprite_pipeline_layout,
+            &shader,
+            config.format,
+            sample_count,
+            "Sprite",
+        );
+        let text_pipeline = create_sprite_pipeline(
+            &device,
+            &sprite_pipeline_layout,
+            &shader,
+            config.format,
+            sample_count,
+            wgpu::BlendState::ALPHA_BLENDING,
+            wgpu::PolygonMode::Fill,
+            "Text Pipeline",
+        );
+
+        let line_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Line Shader"),
+            source: wgpu::ShaderSource::Wgsl(LINE_SHADER.into()),
+        });
+        let line_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Line Pipeline Layout"),
+            bind_group_layouts: &[&scene_bind_group_layout],
             push_constant_ranges: &[],
         });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Sprite Pipeline"),
-            layout: Some(&pipeline_layout),
+        let line_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug Line Pipeline"),
+            layout: Some(&line_pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: &line_shader,
                 entry_point: "vs_main",
-                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                buffers: &[LineVertex::desc()],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: &line_shader,
                 entry_point: "fs_main",
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
                 targets: &[Some(wgpu::ColorTargetState {
@@ -285,13 +857,114 @@ impl Renderer {
                 })],
             }),
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        let shape_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shape Shader"),
+            source: wgpu::ShaderSource::Wgsl(SHAPE_SHADER.into()),
+        });
+        let shape_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shape Pipeline"),
+            layout: Some(&line_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shape_shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc(), ShapeInstance::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shape_shader,
+                entry_point: "fs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
                 ..Default::default()
             },
+            multiview: None,
+        });
+
+        let background_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Background Shader"),
+            source: wgpu::ShaderSource::Wgsl(BACKGROUND_SHADER.into()),
+        });
+        let background_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Background Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let background_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Background Pipeline Layout"),
+                bind_group_layouts: &[&background_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let background_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Background Pipeline"),
+            layout: Some(&background_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &background_shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &background_shader,
+                entry_point: "fs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
             depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
             multiview: None,
         });
+        let background_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Background Uniform Buffer"),
+            size: std::mem::size_of::<BackgroundUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let background_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Background Bind Group"),
+            layout: &background_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: background_buffer.as_entire_binding(),
+            }],
+        });
 
         let vertices = [
             Vertex {
@@ -326,20 +999,28 @@ impl Renderer {
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        let mut scene_uniform = SceneUniform::new();
-        scene_uniform.update(build_view_proj(size, Vec2::ZERO, 1.0));
-        let scene_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        let uniform_size = std::mem::size_of::<SceneUniform>() as u64;
+        let scene_uniform_stride = uniform_size
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+        let scene_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Scene Uniform Buffer"),
-            contents: bytemuck::bytes_of(&scene_uniform),
+            size: scene_uniform_stride * (MAX_VIEWPORTS as u64 + 1),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
+        let scene_uniform = SceneUniform::new(build_view_proj(size, Vec2::ZERO, 1.0));
+        queue.write_buffer(&scene_buffer, 0, bytemuck::bytes_of(&scene_uniform));
 
         let scene_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Scene Bind Group"),
             layout: &scene_bind_group_layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
-                resource: scene_buffer.as_entire_binding(),
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &scene_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(uniform_size),
+                }),
             }],
         });
 
@@ -351,31 +1032,91 @@ impl Renderer {
             mapped_at_creation: false,
         });
 
+        let line_capacity = 2;
+        let line_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug Line Buffer"),
+            size: (line_capacity * std::mem::size_of::<LineVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let shape_capacity = 1;
+        let shape_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shape Instance Buffer"),
+            size: (shape_capacity * std::mem::size_of::<ShapeInstance>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let font = BitmapFont::builtin(&device, &queue);
+        let font_bind_group =
+            create_texture_bind_group(&device, &texture_bind_group_layout, font.texture());
+        let text_capacity = 1;
+        let text_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Instance Buffer"),
+            size: (text_capacity * std::mem::size_of::<InstanceRaw>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         Self {
-            window,
-            surface,
+            surface: None,
+            headless_target: None,
+            limits: device.limits(),
             device,
             queue,
+            adapter_info,
             config,
+            supported_present_modes: vec![wgpu::PresentMode::Fifo],
             size,
-            clear_color: wgpu::Color {
-                r: 0.08,
-                g: 0.1,
-                b: 0.12,
-                a: 1.0,
-            },
-            render_pipeline,
+            sample_count,
+            msaa_view,
+            clear_color: to_wgpu_color(clear_color),
+            background_gradient: None,
+            background_pipeline,
+            background_buffer,
+            background_bind_group,
+            sprite_pipeline_layout,
+            materials: vec![default_material],
+            wireframe: false,
+            bloom: None,
             vertex_buffer,
             index_buffer,
             num_indices: indices.len() as u32,
             instance_buffer,
             instance_capacity,
-            num_instances: 0,
-            scene_uniform,
+            instance_low_frames: 0,
+            instance_upload: InstanceUpload::default(),
+            staging_belt: wgpu::util::StagingBelt::new(STAGING_CHUNK_SIZE),
+            pending_upload: None,
+            batches: Vec::new(),
+            line_pipeline,
+            line_buffer,
+            line_capacity,
+            line_vertex_count: 0,
+            shape_pipeline,
+            shape_buffer,
+            shape_capacity,
+            shape_count: 0,
+            font,
+            font_bind_group,
+            text_pipeline,
+            text_instances: Vec::new(),
+            text_buffer,
+            text_capacity,
+            camera_position: Vec2::ZERO,
+            camera_zoom: 1.0,
+            scale_factor: 1.0,
+            camera_roll: 0.0,
+            pixel_snap: false,
+            stats: RenderStats::default(),
+            frame_stats: RenderStats::default(),
             scene_buffer,
+            scene_uniform_stride,
+            viewports: Vec::new(),
             scene_bind_group,
             texture_bind_group_layout,
-            texture_bind_group: None,
+            texture_bind_groups: Vec::new(),
         }
     }
 
@@ -387,52 +1128,296 @@ impl Renderer {
         &self.queue
     }
 
-    pub fn window(&self) -> &Window {
-        self.window.as_ref()
+    // Name, backend and device type of the GPU in use.
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
     }
 
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width == 0 || new_size.height == 0 {
-            return;
+    // The limits the device was created with.
+    pub fn limits(&self) -> &wgpu::Limits {
+        &self.limits
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    // Takes effect immediately and is kept across resizes.
+    pub fn set_present_mode(&mut self, requested: wgpu::PresentMode) {
+        self.config.present_mode = select_present_mode(requested, &self.supported_present_modes);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+
+    // Replaces any background gradient with a flat color from the next render.
+    pub fn set_clear_color(&mut self, color: [f32; 4]) {
+        self.clear_color = to_wgpu_color(color);
+        self.background_gradient = None;
+    }
+
+    // Used until `set_clear_color` is called.
+    pub fn set_background_gradient(&mut self, top: [f32; 4], bottom: [f32; 4]) {
+        let uniform = BackgroundUniform {
+            top: Color::from(top).to_linear(),
+            bottom: Color::from(bottom).to_linear(),
+        };
+        self.queue
+            .write_buffer(&self.background_buffer, 0, bytemuck::bytes_of(&uniform));
+        self.background_gradient = Some(uniform);
+    }
+
+    // Returns whether wireframe is on; false without POLYGON_MODE_LINE.
+    pub fn set_wireframe(&mut self, enabled: bool) -> bool {
+        let supported = self.materials[0].wireframe.is_some();
+        if enabled && !supported {
+            log::warn!("Wireframe mode is not supported on this device, keeping fill mode");
+        }
+        self.wireframe = enabled && supported;
+        self.wireframe
+    }
+
+    // Compiles WGSL defining `fs_main`, appended to the sprite prelude.
+    pub fn register_material(&mut self, wgsl_source: &str) -> Result<MaterialId, MaterialError> {
+        let id = MaterialId(self.materials.len() as u32);
+        let label = format!("Material {}", id.0);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&label),
+                source: wgpu::ShaderSource::Wgsl(material_source(wgsl_source).into()),
+            });
+        let pipelines = create_material_pipelines(
+            &self.device,
+            &self.sprite_pipeline_layout,
+            &shader,
+            self.config.format,
+            self.sample_count,
+            &label,
+        );
+        if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(MaterialError::Compile(err.to_string()));
+        }
+        self.materials.push(pipelines);
+        log::info!("Registered sprite material {}", id.0);
+        Ok(id)
+    }
+
+    // A full sprite shader, or None for the built-in one; text keeps the built-in.
+    pub fn set_sprite_shader(&mut self, wgsl_source: Option<&str>) -> Result<(), MaterialError> {
+        let source = wgsl_source.map_or_else(|| material_source(DEFAULT_MATERIAL), str::to_owned);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Sprite Shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let pipelines = create_material_pipelines(
+            &self.device,
+            &self.sprite_pipeline_layout,
+            &shader,
+            self.config.format,
+            self.sample_count,
+            "Sprite",
+        );
+        if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(MaterialError::Compile(err.to_string()));
+        }
+        self.materials[0] = pipelines;
+        Ok(())
+    }
+
+    pub fn instance_upload(&self) -> InstanceUpload {
+        self.instance_upload
+    }
+
+    // Takes effect from the next `update_instances`.
+    pub fn set_instance_upload(&mut self, upload: InstanceUpload) {
+        self.instance_upload = upload;
+    }
+
+    // Totals for the last presented frame.
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    // Drops anything counted since the last render.
+    pub fn reset_stats(&mut self) {
+        self.frame_stats = RenderStats::default();
+    }
+
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    // None turns bloom off and frees its targets.
+    pub fn set_bloom(&mut self, settings: Option<BloomSettings>) {
+        match (settings, &mut self.bloom) {
+            (Some(settings), Some(bloom)) => bloom.set_settings(settings),
+            (Some(settings), None) => {
+                self.bloom = Some(Bloom::new(&self.device, self.config.format, settings));
+            }
+            (None, _) => self.bloom = None,
+        }
+    }
+
+    pub fn bloom(&self) -> Option<BloomSettings> {
+        self.bloom.as_ref().map(Bloom::settings)
+    }
+
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
         }
 
         self.size = new_size;
         self.config.width = new_size.width;
         self.config.height = new_size.height;
-        self.surface.configure(&self.device, &self.config);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+        if self.headless_target.is_some() {
+            self.headless_target = Some(create_headless_target(&self.device, new_size));
+        }
+        self.msaa_view = create_msaa_view(
+            &self.device,
+            self.config.format,
+            new_size,
+            self.sample_count,
+        );
     }
 
-    pub fn set_texture(&mut self, texture: &Texture) {
-        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Texture Bind Group"),
-            layout: &self.texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                },
-            ],
-        });
-        self.texture_bind_group = Some(bind_group);
+    // Bind group `i` serves batches with `texture_id == i`.
+    pub fn set_textures<'a>(&mut self, textures: impl IntoIterator<Item = &'a Texture>) {
+        self.texture_bind_groups = textures
+            .into_iter()
+            .map(|texture| {
+                create_texture_bind_group(&self.device, &self.texture_bind_group_layout, texture)
+            })
+            .collect();
     }
 
-    pub fn update_camera(&mut self, position: Vec2, zoom: f32) {
-        self.scene_uniform
-            .update(build_view_proj(self.size, position, zoom));
-        self.queue
-            .write_buffer(&self.scene_buffer, 0, bytemuck::bytes_of(&self.scene_uniform));
+    pub fn set_font(&mut self, font: BitmapFont) {
+        self.font_bind_group = create_texture_bind_group(
+            &self.device,
+            &self.texture_bind_group_layout,
+            font.texture(),
+        );
+        self.font = font;
+    }
+
+    // Queues world-space text for the next render only.
+    pub fn draw_text(&mut self, text: &str, pos: Vec2, scale: f32, color: [f32; 4]) {
+        self.font
+            .layout(text, pos, scale, color, &mut self.text_instances);
+    }
+
+    pub fn update_camera(&mut self, position: Vec2, zoom: f32, pixel_snap: bool) {
+        self.camera_position = position;
+        self.camera_zoom = zoom;
+        self.pixel_snap = pixel_snap;
+        self.write_scene_uniform(self.size);
+    }
+
+    // Takes effect from the next `update_camera`.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        if !(scale_factor > 0.0 && scale_factor.is_finite()) {
+            log::warn!("Ignoring invalid display scale factor {}", scale_factor);
+            return;
+        }
+        self.scale_factor = scale_factor;
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    // The target size in physical pixels.
+    pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.size
+    }
+
+    // The target size in logical pixels.
+    pub fn logical_size(&self) -> Vec2 {
+        Vec2::new(self.size.width as f32, self.size.height as f32) / self.scale_factor
+    }
+
+    fn pixel_zoom(&self, zoom: f32) -> f32 {
+        zoom * self.scale_factor
+    }
+
+    // Radians about the camera position; `screen_to_world` ignores it.
+    pub fn set_camera_roll(&mut self, roll: f32) {
+        self.camera_roll = roll;
+        self.write_scene_uniform(self.size);
+    }
+
+    // Physical window pixels (y down) to world units (y up).
+    pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+        let zoom = self.pixel_zoom(self.camera_zoom);
+        screen_to_world(self.size, self.camera_position, zoom, screen)
+    }
+
+    pub fn world_to_screen(&self, world: Vec2) -> Vec2 {
+        let zoom = self.pixel_zoom(self.camera_zoom);
+        world_to_screen(self.size, self.camera_position, zoom, world)
+    }
+
+    // (min, max) corners of what the camera, or every viewport, shows.
+    pub fn visible_world_rect(&self) -> (Vec2, Vec2) {
+        if self.viewports.is_empty() {
+            return visible_world_rect(
+                self.size,
+                self.camera_position,
+                self.pixel_zoom(self.camera_zoom),
+                self.camera_roll,
+            );
+        }
+        self.viewports
+            .iter()
+            .map(|viewport| {
+                visible_world_rect(
+                    viewport.pixel_size(self.size),
+                    viewport.position,
+                    self.pixel_zoom(viewport.zoom),
+                    viewport.roll,
+                )
+            })
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+            .unwrap_or((Vec2::ZERO, Vec2::ZERO))
+    }
+
+    // Draws the scene once per viewport; an empty list uses the main camera.
+    pub fn set_viewports(&mut self, viewports: &[Viewport]) {
+        if viewports.len() > MAX_VIEWPORTS {
+            log::warn!(
+                "{} viewports requested, only the first {} are drawn",
+                viewports.len(),
+                MAX_VIEWPORTS
+            );
+        }
+        self.viewports.clear();
+        self.viewports
+            .extend(viewports.iter().take(MAX_VIEWPORTS).copied());
+        self.write_scene_uniform(self.size);
+    }
+
+    pub fn viewports(&self) -> &[Viewport] {
+        &self.viewports
     }
 
-    pub fn update_instances(&mut self, instances: &[InstanceRaw]) {
+    pub fn update_instances(&mut self, instances: &[InstanceRaw], batches: &[DrawBatch]) {
         let needed = instances.len().max(1);
-        if needed > self.instance_capacity {
-            self.instance_capacity = needed.next_power_of_two();
-            let size_bytes =
-                (self.instance_capacity * std::mem::size_of::<InstanceRaw>()) as u64;
+        let capacity = next_instance_capacity(
+            needed,
+            self.instance_capacity,
+            &mut self.instance_low_frames,
+        );
+        if capacity != self.instance_capacity {
+            self.instance_capacity = capacity;
+            let size_bytes = (self.instance_capacity * std::mem::size_of::<InstanceRaw>()) as u64;
             self.instance_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
                 label: Some("Instance Buffer"),
                 size: size_bytes,
@@ -441,61 +1426,777 @@ impl Renderer {
             });
         }
 
-        self.num_instances = instances.len() as u32;
-        if !instances.is_empty() {
-            self.queue
-                .write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(instances));
+        self.batches.clear();
+        self.batches.extend_from_slice(batches);
+        let bytes: &[u8] = bytemuck::cast_slice(instances);
+        self.frame_stats.instances += instances.len() as u32;
+        let Some(size) = wgpu::BufferSize::new(bytes.len() as u64) else {
+            return;
+        };
+        self.flush_pending_upload();
+        if self.instance_upload.uses_staging(instances.len()) {
+            self.stage_instances(bytes, size);
+        } else {
+            self.queue.write_buffer(&self.instance_buffer, 0, bytes);
         }
+        self.frame_stats.bytes_uploaded += bytes.len() as u64;
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    fn flush_pending_upload(&mut self) {
+        if let Some(previous) = self.pending_upload.take() {
+            self.queue.submit(Some(previous));
+            self.staging_belt.recall();
+        }
+    }
 
+    fn stage_instances(&mut self, bytes: &[u8], size: wgpu::BufferSize) {
+        self.device.poll(wgpu::Maintain::Poll);
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Instance Upload Encoder"),
+            });
+        self.staging_belt
+            .write_buffer(&mut encoder, &self.instance_buffer, 0, size, &self.device)
+            .copy_from_slice(bytes);
+        self.staging_belt.finish();
+        self.pending_upload = Some(encoder.finish());
+    }
+
+    fn submit_frame(&mut self, encoder: wgpu::CommandEncoder) {
+        let upload = self.pending_upload.take();
+        self.queue
+            .submit(upload.into_iter().chain(Some(encoder.finish())));
+        self.staging_belt.recall();
+    }
+
+    // Vertices are consumed in pairs as a line list and drawn over the sprites.
+    pub fn update_debug_lines(&mut self, vertices: &[LineVertex]) {
+        let needed = vertices.len().max(2);
+        if needed > self.line_capacity {
+            self.line_capacity = needed.next_power_of_two();
+            let size_bytes = (self.line_capacity * std::mem::size_of::<LineVertex>()) as u64;
+            self.line_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Debug Line Buffer"),
+                size: size_bytes,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+        }
+
+        self.line_vertex_count = (vertices.len() & !1) as u32;
+        if self.line_vertex_count > 0 {
+            let bytes: &[u8] = bytemuck::cast_slice(vertices);
+            self.queue.write_buffer(&self.line_buffer, 0, bytes);
+            self.frame_stats.bytes_uploaded += bytes.len() as u64;
+        }
+    }
+
+    pub fn update_shapes(&mut self, shapes: &[ShapeInstance]) {
+        if shapes.len() > self.shape_capacity {
+            self.shape_capacity = shapes.len().next_power_of_two();
+            let size_bytes = (self.shape_capacity * std::mem::size_of::<ShapeInstance>()) as u64;
+            self.shape_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Shape Instance Buffer"),
+                size: size_bytes,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+        }
+
+        self.shape_count = shapes.len() as u32;
+        self.frame_stats.instances += self.shape_count;
+        if !shapes.is_empty() {
+            let bytes: &[u8] = bytemuck::cast_slice(shapes);
+            self.queue.write_buffer(&self.shape_buffer, 0, bytes);
+            self.frame_stats.bytes_uploaded += bytes.len() as u64;
+        }
+    }
+
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.upload_text();
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
 
+        match &self.surface {
+            Some(surface) => {
+                let output = match surface.get_current_texture() {
+                    Ok(output) => output,
+                    Err(err) => {
+                        self.frame_stats = RenderStats::default();
+                        self.text_instances.clear();
+                        return Err(err);
+                    }
+                };
+                let view = output
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                self.prepare_bloom(self.size);
+                self.frame_stats.draw_calls +=
+                    self.encode_output(&mut encoder, &view, self.msaa_view.as_ref(), self.size);
+                self.submit_frame(encoder);
+                output.present();
+            }
+            None => {
+                self.prepare_bloom(self.size);
+                if let Some(target) = &self.headless_target {
+                    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+                    self.frame_stats.draw_calls +=
+                        self.encode_output(&mut encoder, &view, self.msaa_view.as_ref(), self.size);
+                }
+                self.submit_frame(encoder);
+            }
+        }
+        self.text_instances.clear();
+        self.stats = std::mem::take(&mut self.frame_stats);
+        Ok(())
+    }
+
+    // Renders into an owned texture and reads back tightly packed rows, top first.
+    pub fn render_to_texture(&mut self, width: u32, height: u32) -> Result<Vec<u8>, CaptureError> {
+        let width = width.max(1);
+        let height = height.max(1);
+        let format = self.config.format;
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let offscreen_size = winit::dpi::PhysicalSize::new(width, height);
+        let msaa_view = create_msaa_view(&self.device, format, offscreen_size, self.sample_count);
+        self.write_scene_uniform(offscreen_size);
+        self.prepare_bloom(offscreen_size);
+        self.upload_text();
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Offscreen Encoder"),
+            });
+        self.encode_output(&mut encoder, &view, msaa_view.as_ref(), offscreen_size);
+        let pixels = self.read_back(encoder, &texture);
+        self.write_scene_uniform(self.size);
+        pixels
+    }
+
+    // Reads back what the last `render` drew, like `render_to_texture`.
+    pub fn read_pixels(&mut self) -> Result<Vec<u8>, CaptureError> {
+        let Some(target) = self.headless_target.take() else {
+            return self.render_to_texture(self.size.width, self.size.height);
+        };
+        let encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        let pixels = self.read_back(encoder, &target);
+        self.headless_target = Some(target);
+        pixels
+    }
+
+    fn read_back(
+        &mut self,
+        mut encoder: wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> Result<Vec<u8>, CaptureError> {
+        let format = texture.format();
+        let bytes_per_pixel = format
+            .block_copy_size(None)
+            .ok_or(CaptureError::UnsupportedFormat(format))?;
+        let (width, height) = (texture.width(), texture.height());
+
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.submit_frame(encoder);
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .unwrap_or(Err(wgpu::BufferAsyncError))
+            .map_err(CaptureError::Map)?;
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
         {
-            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Sprite Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(self.clear_color),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
+            let data = slice.get_mapped_range();
+            for row in data.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        readback.unmap();
+
+        if matches!(
+            format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        Ok(pixels)
+    }
+
+    // Only formats with 4-byte pixels can be saved.
+    pub fn capture_screenshot(&mut self, path: &Path) -> Result<(), CaptureError> {
+        let format = self.config.format;
+        if format.block_copy_size(None) != Some(4) {
+            return Err(CaptureError::UnsupportedFormat(format));
+        }
+        let width = self.size.width.max(1);
+        let height = self.size.height.max(1);
+        let pixels = self.render_to_texture(width, height)?;
+        image::save_buffer(path, &pixels, width, height, image::ColorType::Rgba8)
+            .map_err(CaptureError::Save)
+    }
+
+    fn pipeline(&self, material: MaterialId, blend: BlendMode) -> &wgpu::RenderPipeline {
+        let pipelines = self
+            .materials
+            .get(material.0 as usize)
+            .unwrap_or(&self.materials[0]);
+        if self.wireframe
+            && let Some(pipeline) = &pipelines.wireframe
+        {
+            return pipeline;
+        }
+        match blend {
+            BlendMode::Alpha => &pipelines.alpha,
+            BlendMode::Additive => &pipelines.additive,
+        }
+    }
+
+    fn upload_text(&mut self) {
+        let needed = self.text_instances.len().max(1);
+        if needed > self.text_capacity {
+            self.text_capacity = needed.next_power_of_two();
+            let size_bytes = (self.text_capacity * std::mem::size_of::<InstanceRaw>()) as u64;
+            self.text_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Text Instance Buffer"),
+                size: size_bytes,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
             });
+        }
+        self.frame_stats.instances += self.text_instances.len() as u32;
+        if !self.text_instances.is_empty() {
+            let bytes: &[u8] = bytemuck::cast_slice(&self.text_instances);
+            self.queue.write_buffer(&self.text_buffer, 0, bytes);
+            self.frame_stats.bytes_uploaded += bytes.len() as u64;
+        }
+    }
+
+    fn write_scene_uniform(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        let main = camera_view_proj(
+            size,
+            self.camera_position,
+            self.pixel_zoom(self.camera_zoom),
+            self.camera_roll,
+            self.pixel_snap,
+        );
+        let scale_factor = self.scale_factor;
+        let views = self.viewports.iter().map(|viewport| {
+            camera_view_proj(
+                viewport.pixel_size(size),
+                viewport.position,
+                viewport.zoom * scale_factor,
+                viewport.roll,
+                self.pixel_snap,
+            )
+        });
+        for (slot, view_proj) in std::iter::once(main).chain(views).enumerate() {
+            let uniform = SceneUniform::new(view_proj);
+            self.queue.write_buffer(
+                &self.scene_buffer,
+                self.scene_offset(slot) as u64,
+                bytemuck::bytes_of(&uniform),
+            );
+            self.frame_stats.bytes_uploaded += std::mem::size_of::<SceneUniform>() as u64;
+        }
+    }
+
+    fn scene_offset(&self, slot: usize) -> u32 {
+        (slot as u64 * self.scene_uniform_stride) as u32
+    }
+
+    fn prepare_bloom(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        if let Some(bloom) = &mut self.bloom {
+            bloom.prepare(&self.device, &self.queue, size);
+        }
+    }
+
+    fn encode_output(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        msaa_view: Option<&wgpu::TextureView>,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> u32 {
+        match &self.bloom {
+            Some(bloom) => {
+                self.encode_frame(encoder, bloom.scene_view(), msaa_view, size)
+                    + bloom.encode(encoder, view)
+            }
+            None => self.encode_frame(encoder, view, msaa_view, size),
+        }
+    }
 
+    fn encode_frame(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        msaa_view: Option<&wgpu::TextureView>,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> u32 {
+        let mut draw_calls = 0;
+        let load = if self.background_gradient.is_some() {
+            self.encode_background_pass(encoder, msaa_view.unwrap_or(view));
+            draw_calls += 1;
+            wgpu::LoadOp::Load
+        } else {
+            wgpu::LoadOp::Clear(self.clear_color)
+        };
+        draw_calls + self.encode_sprite_pass(encoder, view, msaa_view, load, size)
+    }
+
+    fn encode_background_pass(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Background Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.clear_color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.background_pipeline);
+        pass.set_bind_group(0, &self.background_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    fn encode_sprite_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        msaa_view: Option<&wgpu::TextureView>,
+        load: wgpu::LoadOp<wgpu::Color>,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> u32 {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sprite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: msaa_view.unwrap_or(view),
+                resolve_target: msaa_view.map(|_| view),
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        let mut draw_calls = 0;
+        if self.viewports.is_empty() {
+            draw_calls += self.encode_scene(&mut pass, self.scene_offset(0));
+        } else {
+            for (index, viewport) in self.viewports.iter().enumerate() {
+                let [x, y, width, height] = viewport.pixel_rect(size);
+                if width < 1.0 || height < 1.0 {
+                    continue;
+                }
+                pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                draw_calls += self.encode_scene(&mut pass, self.scene_offset(index + 1));
+            }
+            pass.set_viewport(0.0, 0.0, size.width as f32, size.height as f32, 0.0, 1.0);
+        }
+
+        if !self.text_instances.is_empty() {
+            pass.set_pipeline(&self.text_pipeline);
+            pass.set_bind_group(0, &self.scene_bind_group, &[self.scene_offset(0)]);
+            pass.set_bind_group(1, &self.font_bind_group, &[]);
+            pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            pass.set_vertex_buffer(1, self.text_buffer.slice(..));
+            pass.draw_indexed(0..self.num_indices, 0, 0..self.text_instances.len() as u32);
+            draw_calls += 1;
+        }
+        draw_calls
+    }
+
+    fn encode_scene<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, scene_offset: u32) -> u32 {
+        pass.set_bind_group(0, &self.scene_bind_group, &[scene_offset]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        let mut draw_calls = self.batches.len() as u32;
+        let mut current_pipeline = None;
+        for batch in &self.batches {
+            if current_pipeline != Some((batch.material, batch.blend)) {
+                pass.set_pipeline(self.pipeline(batch.material, batch.blend));
+                current_pipeline = Some((batch.material, batch.blend));
+            }
             let texture_bind_group = self
-                .texture_bind_group
-                .as_ref()
+                .texture_bind_groups
+                .get(batch.texture_id as usize)
+                .or_else(|| self.texture_bind_groups.first())
                 .expect("texture bind group");
-
-            pass.set_pipeline(&self.render_pipeline);
-            pass.set_bind_group(0, &self.scene_bind_group, &[]);
             pass.set_bind_group(1, texture_bind_group, &[]);
-            pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-            pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            pass.draw_indexed(0..self.num_indices, 0, 0..self.num_instances);
+            pass.draw_indexed(0..self.num_indices, 0, batch.instances.clone());
         }
 
-        self.queue.submit(Some(encoder.finish()));
-        output.present();
-        Ok(())
+        if self.shape_count > 0 {
+            pass.set_pipeline(&self.shape_pipeline);
+            pass.set_vertex_buffer(1, self.shape_buffer.slice(..));
+            pass.draw_indexed(0..self.num_indices, 0, 0..self.shape_count);
+            draw_calls += 1;
+        }
+
+        if self.line_vertex_count > 0 {
+            pass.set_pipeline(&self.line_pipeline);
+            pass.set_vertex_buffer(0, self.line_buffer.slice(..));
+            pass.draw(0..self.line_vertex_count, 0..1);
+            draw_calls += 1;
+        }
+        draw_calls
     }
 }
 
+fn create_texture_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &Texture,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Texture Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+        ],
+    })
+}
+
+async fn request_device(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+    engine_config: &EngineConfig,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), RendererError> {
+    let request = |force_fallback_adapter| {
+        instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: engine_config.power_preference,
+            compatible_surface,
+            force_fallback_adapter,
+        })
+    };
+    let mut adapter = request(false).await;
+    if adapter.is_none() && engine_config.allow_fallback_adapter {
+        log::warn!("No hardware graphics adapter found, trying the software fallback");
+        adapter = request(true).await;
+    }
+    let adapter = adapter.ok_or(RendererError::NoAdapter)?;
+    let info = adapter.get_info();
+    log::info!(
+        "Using {} on {:?} ({:?}, driver {} {})",
+        info.name,
+        info.backend,
+        info.device_type,
+        info.driver,
+        info.driver_info
+    );
+
+    let required_features = adapter.features() & wgpu::Features::POLYGON_MODE_LINE;
+    if required_features.is_empty() {
+        log::warn!("POLYGON_MODE_LINE is not supported, wireframe mode is unavailable");
+    }
+
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features,
+                required_limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .map_err(RendererError::NoDevice)?;
+    Ok((adapter, device, queue))
+}
+
+fn next_instance_capacity(needed: usize, capacity: usize, low_frames: &mut u32) -> usize {
+    if needed > capacity {
+        *low_frames = 0;
+        return needed.next_power_of_two();
+    }
+    if needed >= capacity / 4 {
+        *low_frames = 0;
+        return capacity;
+    }
+    *low_frames += 1;
+    if *low_frames < INSTANCE_SHRINK_FRAMES {
+        return capacity;
+    }
+    *low_frames = 0;
+    needed.next_power_of_two() * 2
+}
+
+fn material_source(fragment: &str) -> String {
+    format!("{}{}", SPRITE_SHADER_PRELUDE, fragment)
+}
+
+fn create_material_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    label: &str,
+) -> MaterialPipelines {
+    let create = |blend, polygon_mode, suffix: &str| {
+        create_sprite_pipeline(
+            device,
+            layout,
+            shader,
+            format,
+            sample_count,
+            blend,
+            polygon_mode,
+            &format!("{} {}", label, suffix),
+        )
+    };
+    MaterialPipelines {
+        alpha: create(
+            wgpu::BlendState::ALPHA_BLENDING,
+            wgpu::PolygonMode::Fill,
+            "Pipeline",
+        ),
+        additive: create(
+            ADDITIVE_BLENDING,
+            wgpu::PolygonMode::Fill,
+            "Additive Pipeline",
+        ),
+        wireframe: device
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE)
+            .then(|| {
+                create(
+                    wgpu::BlendState::ALPHA_BLENDING,
+                    wgpu::PolygonMode::Line,
+                    "Wireframe Pipeline",
+                )
+            }),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_sprite_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    blend: wgpu::BlendState,
+    polygon_mode: wgpu::PolygonMode,
+    label: &str,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[Vertex::desc(), InstanceRaw::desc()],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            polygon_mode,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    })
+}
+
+fn select_sample_count(requested: u32, flags: wgpu::TextureFormatFeatureFlags) -> u32 {
+    let selected = [4, 2]
+        .into_iter()
+        .find(|&count| count <= requested && flags.sample_count_supported(count))
+        .unwrap_or(1);
+    if selected != requested.max(1) {
+        log::warn!(
+            "MSAA x{} is not supported for the surface format, using x{}",
+            requested,
+            selected
+        );
+    }
+    selected
+}
+
+fn create_headless_target(
+    device: &wgpu::Device,
+    size: winit::dpi::PhysicalSize<u32>,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Headless Target"),
+        size: wgpu::Extent3d {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HEADLESS_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+fn create_msaa_view(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    size: winit::dpi::PhysicalSize<u32>,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count <= 1 {
+        return None;
+    }
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("MSAA Color Target"),
+        size: wgpu::Extent3d {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+fn select_present_mode(
+    requested: wgpu::PresentMode,
+    supported: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    use wgpu::PresentMode::{AutoNoVsync, AutoVsync, Fifo, FifoRelaxed, Immediate, Mailbox};
+
+    let fallbacks: &[wgpu::PresentMode] = match requested {
+        Fifo | FifoRelaxed | AutoVsync => &[Fifo],
+        Mailbox | Immediate | AutoNoVsync => &[Mailbox, Immediate, Fifo],
+    };
+    let selected = std::iter::once(requested)
+        .chain(fallbacks.iter().copied())
+        .find(|mode| matches!(mode, AutoVsync | AutoNoVsync) || supported.contains(mode))
+        .unwrap_or(Fifo);
+
+    if selected == requested {
+        log::info!("Present mode: {:?}", selected);
+    } else {
+        log::warn!(
+            "Present mode {:?} is unsupported, using {:?} (supported: {:?})",
+            requested,
+            selected,
+            supported
+        );
+    }
+    selected
+}
+
+fn to_wgpu_color(color: [f32; 4]) -> wgpu::Color {
+    let color = Color::from(color).to_linear();
+    wgpu::Color {
+        r: color[0] as f64,
+        g: color[1] as f64,
+        b: color[2] as f64,
+        a: color[3] as f64,
+    }
+}
+
+fn camera_view_proj(
+    size: winit::dpi::PhysicalSize<u32>,
+    position: Vec2,
+    zoom: f32,
+    roll: f32,
+    pixel_snap: bool,
+) -> glam::Mat4 {
+    let position = if pixel_snap {
+        snap_to_pixel(size, position, zoom)
+    } else {
+        position
+    };
+    let roll = glam::Mat4::from_translation(position.extend(0.0))
+        * glam::Mat4::from_rotation_z(-roll)
+        * glam::Mat4::from_translation(-position.extend(0.0));
+    build_view_proj(size, position, zoom) * roll
+}
+
 fn build_view_proj(
     size: winit::dpi::PhysicalSize<u32>,
     camera_pos: Vec2,
@@ -510,3 +2211,394 @@ fn build_view_proj(
 
     glam::Mat4::orthographic_rh(left, right, bottom, top, -1.0, 1.0)
 }
+
+// Moves the camera so the view's edges fall on whole screen pixels.
+pub fn snap_to_pixel(
+    size: winit::dpi::PhysicalSize<u32>,
+    camera_pos: Vec2,
+    camera_zoom: f32,
+) -> Vec2 {
+    let half = Vec2::new(size.width.max(1) as f32, size.height.max(1) as f32) * 0.5;
+    ((camera_pos * camera_zoom - half).round() + half) / camera_zoom
+}
+
+pub fn screen_to_world(
+    size: winit::dpi::PhysicalSize<u32>,
+    camera_pos: Vec2,
+    camera_zoom: f32,
+    screen: Vec2,
+) -> Vec2 {
+    let width = size.width.max(1) as f32;
+    let height = size.height.max(1) as f32;
+    let ndc = Vec2::new(screen.x / width * 2.0 - 1.0, 1.0 - screen.y / height * 2.0);
+    let inverse = build_view_proj(size, camera_pos, camera_zoom).inverse();
+    inverse.transform_point3(ndc.extend(0.0)).truncate()
+}
+
+// Axis-aligned bounds of the view, covering a rolled camera's corners.
+pub fn visible_world_rect(
+    size: winit::dpi::PhysicalSize<u32>,
+    camera_pos: Vec2,
+    camera_zoom: f32,
+    camera_roll: f32,
+) -> (Vec2, Vec2) {
+    let half = Vec2::new(size.width.max(1) as f32, size.height.max(1) as f32) * 0.5 / camera_zoom;
+    let (sin, cos) = camera_roll.sin_cos();
+    let (sin, cos) = (sin.abs(), cos.abs());
+    let extent = Vec2::new(cos * half.x + sin * half.y, sin * half.x + cos * half.y);
+    (camera_pos - extent, camera_pos + extent)
+}
+
+pub fn world_to_screen(
+    size: winit::dpi::PhysicalSize<u32>,
+    camera_pos: Vec2,
+    camera_zoom: f32,
+    world: Vec2,
+) -> Vec2 {
+    let width = size.width.max(1) as f32;
+    let height = size.height.max(1) as f32;
+    let ndc = build_view_proj(size, camera_pos, camera_zoom).transform_point3(world.extend(0.0));
+    Vec2::new((ndc.x + 1.0) * 0.5 * width, (1.0 - ndc.y) * 0.5 * height)
+}
+
+#[cfg(test)]
+pub(crate) fn headless(width: u32, height: u32) -> Renderer {
+    pollster::block_on(Renderer::new_headless(width, height))
+        .unwrap_or_else(|err| panic!("{}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_split_where_texture_changes() {
+        let keys = [0, 0, 1, 1, 1, 0];
+        let batches =
+            build_batches(keys.map(|texture| (texture, BlendMode::Alpha, MaterialId::DEFAULT)));
+        let ranges: Vec<(u32, Range<u32>)> = batches
+            .into_iter()
+            .map(|batch| (batch.texture_id, batch.instances))
+            .collect();
+        assert_eq!(ranges, vec![(0, 0..2), (1, 2..5), (0, 5..6)]);
+    }
+
+    #[test]
+    fn world_screen_round_trip() {
+        let size = winit::dpi::PhysicalSize::new(800, 600);
+        let camera = Vec2::new(12.0, -3.5);
+        for zoom in [0.5, 1.0, 3.0] {
+            for world in [Vec2::ZERO, Vec2::new(40.0, 25.0), Vec2::new(-100.0, 7.25)] {
+                let screen = world_to_screen(size, camera, zoom, world);
+                let back = screen_to_world(size, camera, zoom, screen);
+                assert!(
+                    (back - world).length() < 1e-3,
+                    "{world} -> {screen} -> {back}"
+                );
+            }
+        }
+        let center = world_to_screen(size, camera, 2.0, camera);
+        assert!((center - Vec2::new(400.0, 300.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn flips_swap_the_tile_uv_bounds() {
+        let atlas = Atlas::new(2, 2, 16);
+        let mut sprite = Sprite::new(Vec2::ONE, 1, [1.0; 4]);
+        let plain = InstanceRaw::from_components(&Transform::new(Vec2::ZERO), &sprite, &atlas);
+        assert_eq!((plain.uv_min, plain.uv_max), ([0.5, 0.0], [1.0, 0.5]));
+
+        sprite.flip_x = true;
+        let flipped = InstanceRaw::from_components(&Transform::new(Vec2::ZERO), &sprite, &atlas);
+        assert_eq!((flipped.uv_min, flipped.uv_max), ([1.0, 0.0], [0.5, 0.5]));
+
+        sprite.flip_y = true;
+        let both = InstanceRaw::from_components(&Transform::new(Vec2::ZERO), &sprite, &atlas);
+        assert_eq!((both.uv_min, both.uv_max), ([1.0, 0.5], [0.5, 0.0]));
+    }
+
+    #[test]
+    fn blend_change_splits_batches() {
+        let keys = [BlendMode::Alpha, BlendMode::Alpha, BlendMode::Additive]
+            .map(|blend| (0, blend, MaterialId::DEFAULT));
+        let batches = build_batches(keys);
+        assert_eq!(batches.len(), 2);
+        assert_eq!(
+            (batches[0].blend, batches[0].instances.clone()),
+            (BlendMode::Alpha, 0..2)
+        );
+        assert_eq!(
+            (batches[1].blend, batches[1].instances.clone()),
+            (BlendMode::Additive, 2..3)
+        );
+    }
+
+    #[test]
+    fn renderer_errors_explain_themselves() {
+        let no_adapter = RendererError::NoAdapter.to_string();
+        assert!(no_adapter.contains("no compatible graphics adapter"));
+        assert!(no_adapter.contains("Vulkan"));
+        assert_eq!(
+            RendererError::UnsupportedSurface.to_string(),
+            "the graphics adapter cannot present to this window"
+        );
+    }
+
+    #[test]
+    fn instance_capacity_grows_at_once_and_shrinks_after_a_quiet_spell() {
+        let mut low_frames = 0;
+        let mut capacity = next_instance_capacity(1, 1, &mut low_frames);
+        assert_eq!(capacity, 1);
+        capacity = next_instance_capacity(3000, capacity, &mut low_frames);
+        assert_eq!(capacity, 4096);
+
+        for _ in 0..INSTANCE_SHRINK_FRAMES - 1 {
+            capacity = next_instance_capacity(100, capacity, &mut low_frames);
+        }
+        assert_eq!(capacity, 4096);
+        capacity = next_instance_capacity(2000, capacity, &mut low_frames);
+        assert_eq!((capacity, low_frames), (4096, 0));
+
+        for _ in 0..INSTANCE_SHRINK_FRAMES - 1 {
+            capacity = next_instance_capacity(100, capacity, &mut low_frames);
+        }
+        assert_eq!(capacity, 4096);
+        capacity = next_instance_capacity(100, capacity, &mut low_frames);
+        assert_eq!(capacity, 256);
+    }
+
+    #[test]
+    fn snapping_puts_the_view_edges_on_whole_pixels() {
+        let camera = Vec2::new(10.3, -4.2);
+        let even = snap_to_pixel(winit::dpi::PhysicalSize::new(800, 600), camera, 2.0);
+        assert!((even - Vec2::new(10.5, -4.0)).length() < 1e-5);
+        let odd = snap_to_pixel(winit::dpi::PhysicalSize::new(801, 601), camera, 2.0);
+        assert!((odd - Vec2::new(10.25, -4.25)).length() < 1e-5);
+        for (size, snapped) in [((800.0, 600.0), even), ((801.0, 601.0), odd)] {
+            let edge = snapped * 2.0 - Vec2::new(size.0, size.1) * 0.5;
+            assert_eq!(edge, edge.round());
+        }
+    }
+
+    fn bind_white_texture(renderer: &mut Renderer) {
+        let texture = Texture::from_rgba8(
+            renderer.device(),
+            renderer.queue(),
+            1,
+            1,
+            &[255; 4],
+            "Test Texture",
+            crate::assets::TextureOptions::default(),
+        )
+        .expect("1x1 texture");
+        renderer.set_textures([&texture]);
+        renderer.set_clear_color([0.0, 0.0, 0.0, 1.0]);
+        renderer.update_camera(Vec2::ZERO, 1.0, false);
+    }
+
+    fn white_square(position: Vec2, size: f32) -> InstanceRaw {
+        let sprite = Sprite::new(Vec2::splat(size), 0, [1.0; 4]);
+        InstanceRaw::from_components(&Transform::new(position), &sprite, &Atlas::new(1, 1, 1))
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn stats_count_the_uploaded_instances() {
+        let mut renderer = headless(32, 32);
+        bind_white_texture(&mut renderer);
+        renderer.reset_stats();
+        let instances = [-8.0, 0.0, 8.0].map(|x| white_square(Vec2::new(x, 0.0), 4.0));
+        let batches = build_batches([(0, BlendMode::Alpha, MaterialId::DEFAULT); 3]);
+        renderer.update_instances(&instances, &batches);
+        assert_eq!(renderer.stats(), RenderStats::default());
+        renderer.render().expect("headless render");
+        let stats = renderer.stats();
+        assert_eq!(stats.instances, 3);
+        assert_eq!(
+            stats.bytes_uploaded,
+            3 * std::mem::size_of::<InstanceRaw>() as u64
+        );
+        assert!(stats.draw_calls >= 1);
+    }
+
+    fn pixel(pixels: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let start = ((y * width + x) * 4) as usize;
+        pixels[start..start + 4].try_into().unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn registered_material_draws_in_its_own_batch() {
+        let mut renderer = headless(32, 32);
+        bind_white_texture(&mut renderer);
+        let red = renderer
+            .register_material(
+                "@fragment\nfn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {\n    return vec4<f32>(1.0, 0.0, 0.0, 1.0);\n}\n",
+            )
+            .expect("trivial material compiles");
+        assert_eq!(red, MaterialId(1));
+
+        let instances = [
+            white_square(Vec2::new(-8.0, 0.0), 8.0),
+            white_square(Vec2::new(8.0, 0.0), 8.0),
+        ];
+        let batches = build_batches([
+            (0, BlendMode::Alpha, MaterialId::DEFAULT),
+            (0, BlendMode::Alpha, red),
+        ]);
+        assert_eq!(batches.len(), 2);
+        renderer.update_instances(&instances, &batches);
+        renderer.render().expect("headless render");
+        let pixels = renderer.read_pixels().expect("readback");
+        assert_eq!(pixel(&pixels, 32, 8, 16), [255; 4]);
+        assert_eq!(pixel(&pixels, 32, 24, 16), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn nine_slice_cuts_a_panel_into_nine_quads() {
+        let atlas = Atlas::new(1, 1, 48);
+        let sprite = Sprite::new(Vec2::new(96.0, 48.0), 0, [1.0; 4]);
+        let transform = Transform::new(Vec2::new(100.0, 0.0));
+        let pieces = InstanceRaw::nine_slice(&transform, &sprite, &atlas, NineSlice::uniform(16.0));
+        let close = |a: [f32; 2], b: [f32; 2]| (Vec2::from(a) - Vec2::from(b)).length() < 1e-4;
+        let third = 1.0 / 3.0;
+        let expected = [
+            (0, [60.0, 16.0], [16.0, 16.0], [0.0, 0.0], [third, third]),
+            (
+                4,
+                [100.0, 0.0],
+                [64.0, 16.0],
+                [third, third],
+                [2.0 * third, 2.0 * third],
+            ),
+            (
+                8,
+                [140.0, -16.0],
+                [16.0, 16.0],
+                [2.0 * third, 2.0 * third],
+                [1.0, 1.0],
+            ),
+        ];
+        for (piece, position, size, uv_min, uv_max) in expected {
+            let instance = &pieces[piece];
+            assert!(close(instance.position, position), "piece {piece}");
+            assert!(close(instance.size, size), "piece {piece}");
+            assert!(close(instance.uv_min, uv_min), "piece {piece}");
+            assert!(close(instance.uv_max, uv_max), "piece {piece}");
+        }
+        let area: f32 = pieces
+            .iter()
+            .map(|piece| piece.size[0] * piece.size[1])
+            .sum();
+        assert!((area - 96.0 * 48.0).abs() < 1e-2);
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn split_screen_halves_show_their_own_cameras() {
+        let mut renderer = headless(32, 16);
+        bind_white_texture(&mut renderer);
+        renderer.set_viewports(&[
+            Viewport::new([0.0, 0.0, 0.5, 1.0], Vec2::ZERO, 1.0),
+            Viewport::new([0.5, 0.0, 0.5, 1.0], Vec2::new(100.0, 0.0), 1.0),
+        ]);
+        let batches = build_batches([(0, BlendMode::Alpha, MaterialId::DEFAULT)]);
+        renderer.update_instances(&[white_square(Vec2::ZERO, 8.0)], &batches);
+        renderer.render().expect("headless render");
+        let pixels = renderer.read_pixels().expect("readback");
+        assert_eq!(pixel(&pixels, 32, 8, 8), [255; 4]);
+        assert_eq!(pixel(&pixels, 32, 24, 8), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn hidpi_projection_spans_the_logical_size() {
+        let size = winit::dpi::PhysicalSize::new(800, 600);
+        let corner = build_view_proj(size, Vec2::ZERO, 1.0 * 2.0)
+            .transform_point3(glam::Vec3::new(200.0, 150.0, 0.0))
+            .truncate();
+        assert!((corner - Vec2::ONE).length() < 1e-5);
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn hidpi_renderer_reports_the_logical_size() {
+        let mut renderer = headless(800, 600);
+        renderer.set_scale_factor(2.0);
+        renderer.update_camera(Vec2::ZERO, 1.0, false);
+        assert_eq!(renderer.logical_size(), Vec2::new(400.0, 300.0));
+        assert_eq!(renderer.pixel_zoom(1.5), 3.0);
+        assert_eq!(
+            renderer.visible_world_rect(),
+            (Vec2::new(-200.0, -150.0), Vec2::new(200.0, 150.0))
+        );
+    }
+
+    fn draw_white_square(renderer: &mut Renderer) {
+        bind_white_texture(renderer);
+        let instance = white_square(Vec2::ZERO, 16.0);
+        let batches = build_batches([(0, BlendMode::Alpha, MaterialId::DEFAULT)]);
+        renderer.update_instances(&[instance], &batches);
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn sprite_shader_swaps_in_and_bad_wgsl_keeps_the_old_one() {
+        let mut renderer = headless(32, 32);
+        draw_white_square(&mut renderer);
+        let green = material_source(
+            "@fragment\nfn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {\n    return vec4<f32>(0.0, 1.0, 0.0, 1.0);\n}\n",
+        );
+        renderer
+            .set_sprite_shader(Some(&green))
+            .expect("valid shader");
+        renderer.render().expect("headless render");
+        let pixels = renderer.read_pixels().expect("readback");
+        assert_eq!(pixel(&pixels, 32, 16, 16), [0, 255, 0, 255]);
+
+        let result = renderer.set_sprite_shader(Some("fn fs_main( {"));
+        assert!(matches!(result, Err(MaterialError::Compile(_))));
+        draw_white_square(&mut renderer);
+        renderer.render().expect("headless render");
+        let pixels = renderer.read_pixels().expect("readback");
+        assert_eq!(pixel(&pixels, 32, 16, 16), [0, 255, 0, 255]);
+
+        renderer.set_sprite_shader(None).expect("built-in shader");
+        draw_white_square(&mut renderer);
+        renderer.render().expect("headless render");
+        let pixels = renderer.read_pixels().expect("readback");
+        assert_eq!(pixel(&pixels, 32, 16, 16), [255; 4]);
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn render_to_texture_draws_over_the_background() {
+        let mut renderer = headless(64, 64);
+        draw_white_square(&mut renderer);
+        let pixels = renderer.render_to_texture(64, 64).expect("readback");
+        assert_eq!(pixels.len(), 64 * 64 * 4);
+        assert_eq!(pixel(&pixels, 64, 32, 32), [255; 4]);
+        assert_eq!(pixel(&pixels, 64, 2, 2), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn headless_render_can_be_read_back() {
+        let mut renderer = headless(48, 32);
+        draw_white_square(&mut renderer);
+        renderer.render().expect("headless render");
+        let pixels = renderer.read_pixels().expect("readback");
+        assert_eq!(pixels.len(), 48 * 32 * 4);
+        assert_eq!(pixel(&pixels, 48, 24, 16), [255; 4]);
+        assert_eq!(pixel(&pixels, 48, 1, 1), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn queued_text_lasts_one_render() {
+        let mut renderer = headless(32, 32);
+        renderer.draw_text("HI", Vec2::ZERO, 1.0, [1.0; 4]);
+        assert!(!renderer.text_instances.is_empty());
+        renderer.render().expect("headless render");
+        assert!(renderer.text_instances.is_empty());
+    }
+}
@@ -1,12 +1,29 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
 use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::assets::{Assets, Atlas};
+use crate::color::Color;
+use crate::tilemap::TileMap;
 
-pub type Entity = u32;
+const FACE_VELOCITY_MIN_SPEED: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Entity {
+    pub index: u32,
+    pub generation: u32,
+}
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Transform {
     pub position: Vec2,
     pub rotation: f32,
     pub scale: Vec2,
+    // Painter depth within a layer: higher draws on top.
+    pub z: f32,
 }
 
 impl Transform {
@@ -15,32 +32,296 @@ impl Transform {
             position,
             rotation: 0.0,
             scale: Vec2::ONE,
+            z: 0.0,
         }
     }
 }
 
-#[derive(Clone, Copy)]
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new(Vec2::ZERO)
+    }
+}
+
+// Kinematic bodies push dynamic ones and are never pushed back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyKind {
+    #[default]
+    Dynamic,
+    Kinematic,
+}
+
+// What a body does when its position leaves the world bounds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundsBehavior {
+    // Reflects off the edge, scaled by `Body::bounce`.
+    #[default]
+    Bounce,
+    // Reappears at the opposite edge, asteroids-style.
+    Wrap,
+    // Stops at the edge, losing the velocity that carried it out.
+    Clamp,
+    // Reported by `World::step_physics` so the caller can despawn it.
+    Destroy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Body {
+    pub kind: BodyKind,
     pub velocity: Vec2,
     pub damping: f32,
+    // Restitution: 0 stops dead on impact, 1 bounces back at full speed.
     pub bounce: f32,
+    // Share of the sliding speed one contact can remove, from 0 (ice) to 1.
+    pub friction: f32,
+    // Collisions push lighter bodies further; a mass of 0 ignores gravity.
+    pub mass: f32,
+    pub use_gravity: bool,
+    // Speed limit applied after each physics step; 0 means unlimited.
+    pub max_speed: f32,
+    // Slower movement keeps the current direction.
+    pub min_speed: f32,
+    pub bounds_behavior: BoundsBehavior,
+    // An inactive body is skipped by `step_physics` and collides like a static sprite.
+    pub active: bool,
+    // Turns the transform toward the direction of travel.
+    pub face_velocity: bool,
 }
 
 impl Body {
     pub fn new(velocity: Vec2) -> Self {
         Self {
+            kind: BodyKind::Dynamic,
             velocity,
             damping: 0.4,
             bounce: 0.75,
+            friction: 0.0,
+            mass: 1.0,
+            use_gravity: true,
+            max_speed: 0.0,
+            min_speed: 0.0,
+            bounds_behavior: BoundsBehavior::Bounce,
+            active: true,
+            face_velocity: false,
+        }
+    }
+
+    pub fn kinematic(velocity: Vec2) -> Self {
+        Self {
+            kind: BodyKind::Kinematic,
+            ..Self::new(velocity)
+        }
+    }
+
+    pub fn is_kinematic(&self) -> bool {
+        self.kind == BodyKind::Kinematic
+    }
+
+    fn is_pushable(&self) -> bool {
+        self.active && !self.is_kinematic()
+    }
+
+    fn inverse_mass(&self) -> f32 {
+        if self.mass > 0.0 {
+            1.0 / self.mass
+        } else {
+            1.0
+        }
+    }
+
+    pub fn affected_by_gravity(&self) -> bool {
+        self.use_gravity && self.mass > 0.0 && !self.is_kinematic()
+    }
+
+    // Rotation pointing +x along the velocity, when `face_velocity` applies.
+    pub fn facing_angle(&self) -> Option<f32> {
+        if !self.face_velocity
+            || self.velocity.length_squared() < FACE_VELOCITY_MIN_SPEED * FACE_VELOCITY_MIN_SPEED
+        {
+            return None;
+        }
+        Some(self.velocity.y.atan2(self.velocity.x))
+    }
+
+    fn clamp_speed(&mut self) {
+        if self.max_speed > 0.0 {
+            self.velocity = self.velocity.clamp_length_max(self.max_speed);
+        }
+        if self.velocity.length_squared() < self.min_speed * self.min_speed {
+            self.velocity = Vec2::ZERO;
+        }
+    }
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Self::new(Vec2::ZERO)
+    }
+}
+
+// Collision shape, sized in world units before the entity's world scale.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Collider {
+    // The sprite's box at its world scale; rotation is ignored.
+    #[default]
+    Aabb,
+    // Scaled by the larger axis of the world scale.
+    Circle {
+        radius: f32,
+    },
+}
+
+impl Collider {
+    fn is_aabb(&self) -> bool {
+        matches!(self, Collider::Aabb)
+    }
+}
+
+// A pair collides only when each side's `layers` are in the other's mask.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CollisionLayers {
+    pub layers: u32,
+    pub collision_mask: u32,
+}
+
+impl CollisionLayers {
+    pub const fn new(layers: u32, collision_mask: u32) -> Self {
+        Self {
+            layers,
+            collision_mask,
+        }
+    }
+
+    pub fn interacts_with(self, other: CollisionLayers) -> bool {
+        self.layers & other.collision_mask != 0 && other.layers & self.collision_mask != 0
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        Self::new(1, u32::MAX)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Shape {
+    Box { center: Vec2, half: Vec2 },
+    Circle { center: Vec2, radius: f32 },
+}
+
+impl Shape {
+    fn new(collider: Collider, sprite: &Sprite, transform: &Transform) -> Self {
+        match collider {
+            Collider::Aabb => Shape::Box {
+                center: transform.position + sprite.center_offset(transform),
+                half: (sprite.size * transform.scale).abs() * 0.5,
+            },
+            Collider::Circle { radius } => Shape::Circle {
+                center: transform.position + sprite.center_offset(transform),
+                radius: radius.abs() * transform.scale.abs().max_element(),
+            },
+        }
+    }
+
+    fn bounds(&self) -> (Vec2, Vec2) {
+        match *self {
+            Shape::Box { center, half } => (center, half),
+            Shape::Circle { center, radius } => (center, Vec2::splat(radius)),
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerPhase {
+    Enter,
+    Exit,
+}
+
+// Reported once when a trigger overlap starts and once when it ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TriggerEvent {
+    pub trigger: Entity,
+    pub other: Entity,
+    pub phase: TriggerPhase,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CollisionEvent {
+    pub a: Entity,
+    pub b: Entity,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationEvent {
+    // A non-looping animation reached its last frame.
+    Finished,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackMode {
+    #[default]
+    Loop,
+    // Stops on the last frame and reports `finished()`.
+    Once,
+    // Runs forward then backward without repeating the end frames.
+    PingPong,
+}
+
+// How a sprite's color combines with what is already drawn behind it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    #[default]
+    Alpha,
+    // Adds light on top of the scene; good for glows and particles.
+    Additive,
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct MaterialId(pub u32);
+
+impl MaterialId {
+    pub const DEFAULT: MaterialId = MaterialId(0);
+}
+
+// Draws the tile as nine pieces so a panel can be resized without stretching its frame.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NineSlice {
+    pub border: [f32; 4],
+}
+
+impl NineSlice {
+    pub fn new(border: [f32; 4]) -> Self {
+        Self { border }
+    }
+
+    pub fn uniform(border: f32) -> Self {
+        Self::new([border; 4])
+    }
+}
+
 pub struct Animation {
     frames: Vec<u32>,
     fps: f32,
     timer: f32,
     current: usize,
+    mode: PlaybackMode,
+    reversing: bool,
+    finished: bool,
+    pausable: bool,
+    clip: Option<String>,
 }
 
 impl Animation {
@@ -50,9 +331,53 @@ impl Animation {
             fps,
             timer: 0.0,
             current: 0,
+            mode: PlaybackMode::Loop,
+            reversing: false,
+            finished: false,
+            pausable: true,
+            clip: None,
         }
     }
 
+    pub fn with_clip(mut self, name: impl Into<String>) -> Self {
+        self.clip = Some(name.into());
+        self
+    }
+
+    pub fn clip(&self) -> Option<&str> {
+        self.clip.as_deref()
+    }
+
+    pub fn with_mode(mut self, mode: PlaybackMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_pausable(mut self, pausable: bool) -> Self {
+        self.pausable = pausable;
+        self
+    }
+
+    pub fn pausable(&self) -> bool {
+        self.pausable
+    }
+
+    pub fn frames(&self) -> &[u32] {
+        &self.frames
+    }
+
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    pub fn mode(&self) -> PlaybackMode {
+        self.mode
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
     pub fn update(&mut self, dt: f32) -> Option<u32> {
         if self.frames.is_empty() || self.fps <= 0.0 {
             return None;
@@ -60,190 +385,1710 @@ impl Animation {
 
         let frame_time = 1.0 / self.fps;
         self.timer += dt;
-        while self.timer >= frame_time {
+        while self.timer >= frame_time && !self.finished {
             self.timer -= frame_time;
-            self.current = (self.current + 1) % self.frames.len();
+            self.advance_frame();
+        }
+        if self.finished {
+            self.timer = 0.0;
         }
         Some(self.frames[self.current])
     }
-}
 
-pub struct Sprite {
-    pub size: Vec2,
-    pub tile_index: u32,
-    pub color: [f32; 4],
-    pub spin: f32,
-    pub animation: Option<Animation>,
+    fn advance_frame(&mut self) {
+        let last = self.frames.len() - 1;
+        match self.mode {
+            PlaybackMode::Loop => self.current = (self.current + 1) % self.frames.len(),
+            PlaybackMode::Once => {
+                self.current = (self.current + 1).min(last);
+                self.finished = self.current == last;
+            }
+            PlaybackMode::PingPong => {
+                if last == 0 {
+                    return;
+                }
+                if self.reversing && self.current == 0 {
+                    self.reversing = false;
+                } else if !self.reversing && self.current == last {
+                    self.reversing = true;
+                }
+                if self.reversing {
+                    self.current -= 1;
+                } else {
+                    self.current += 1;
+                }
+            }
+        }
+    }
 }
 
-pub struct World {
-    transforms: Vec<Option<Transform>>,
-    sprites: Vec<Option<Sprite>>,
-    bodies: Vec<Option<Body>>,
-    parents: Vec<Option<Entity>>,
-    world_cache: Vec<Option<Transform>>,
-    free: Vec<Entity>,
+// Lerps a sprite's alpha over `duration` seconds, then removes itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fade {
+    pub from: f32,
+    pub to: f32,
+    pub duration: f32,
+    elapsed: f32,
 }
 
-impl World {
-    pub fn new() -> Self {
+impl Fade {
+    pub fn new(from: f32, to: f32, duration: f32) -> Self {
         Self {
-            transforms: Vec::new(),
-            sprites: Vec::new(),
-            bodies: Vec::new(),
-            parents: Vec::new(),
-            world_cache: Vec::new(),
-            free: Vec::new(),
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
         }
     }
 
-    pub fn spawn_sprite(&mut self, transform: Transform, sprite: Sprite) -> Entity {
-        self.spawn_sprite_with_body(transform, sprite, None)
+    fn update(&mut self, dt: f32) -> f32 {
+        self.elapsed += dt;
+        self.from + (self.to - self.from) * self.progress()
     }
 
-    pub fn spawn_sprite_with_body(
-        &mut self,
-        transform: Transform,
-        sprite: Sprite,
-        body: Option<Body>,
-    ) -> Entity {
-        if let Some(entity) = self.free.pop() {
-            let index = entity as usize;
-            if index >= self.transforms.len() {
-                return self.push_new(transform, sprite, body);
-            }
-            self.transforms[index] = Some(transform);
-            self.sprites[index] = Some(sprite);
-            self.bodies[index] = body;
-            self.parents[index] = None;
-            self.world_cache[index] = None;
-            entity
+    fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            1.0
         } else {
-            self.push_new(transform, sprite, body)
+            (self.elapsed / self.duration).clamp(0.0, 1.0)
         }
     }
 
-    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
-        let index = child as usize;
-        if index >= self.parents.len() {
-            return;
+    pub fn finished(&self) -> bool {
+        self.progress() >= 1.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    #[default]
+    Down,
+    Up,
+    Left,
+    Right,
+}
+
+impl Direction {
+    // The dominant axis of `v`; None when `v` is no longer than `min_length`.
+    pub fn from_vector(v: Vec2, min_length: f32) -> Option<Self> {
+        if v.length_squared() <= min_length * min_length {
+            return None;
         }
-        self.parents[index] = Some(parent);
-        self.world_cache[index] = None;
+        Some(if v.x.abs() >= v.y.abs() {
+            if v.x < 0.0 {
+                Direction::Left
+            } else {
+                Direction::Right
+            }
+        } else if v.y < 0.0 {
+            Direction::Down
+        } else {
+            Direction::Up
+        })
     }
 
-    pub fn get_transform_mut(&mut self, entity: Entity) -> Option<&mut Transform> {
-        self.transforms.get_mut(entity as usize)?.as_mut()
+    fn row(self) -> u32 {
+        match self {
+            Direction::Down => 0,
+            Direction::Up => 1,
+            Direction::Left => 2,
+            Direction::Right => 3,
+        }
     }
+}
 
-    pub fn get_sprite_mut(&mut self, entity: Entity) -> Option<&mut Sprite> {
-        self.sprites.get_mut(entity as usize)?.as_mut()
+// Selects tiles from a 4-direction sheet with one row per `Direction`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Facing {
+    pub direction: Direction,
+    // Tile index of the first tile in the down row.
+    pub base_tile: u32,
+    // Tiles from one direction's row to the next, usually the sheet's columns.
+    pub row_stride: u32,
+    // Slower movement keeps the current direction.
+    pub min_speed: f32,
+}
+
+impl Facing {
+    pub fn new(base_tile: u32, row_stride: u32) -> Self {
+        Self {
+            direction: Direction::Down,
+            base_tile,
+            row_stride,
+            min_speed: 1.0,
+        }
     }
 
-    pub fn step_physics(&mut self, dt: f32, bounds: Vec2) {
-        for index in 0..self.transforms.len() {
-            let (Some(transform), Some(body)) = (
-                self.transforms[index].as_mut(),
-                self.bodies[index].as_mut(),
-            ) else {
-                continue;
-            };
+    pub fn update(&mut self, velocity: Vec2) {
+        if let Some(direction) = Direction::from_vector(velocity, self.min_speed) {
+            self.direction = direction;
+        }
+    }
 
-            let damping = (1.0 - body.damping * dt).clamp(0.0, 1.0);
-            body.velocity *= damping;
-            transform.position += body.velocity * dt;
+    pub fn tile(&self, column: u32) -> u32 {
+        self.base_tile + self.direction.row() * self.row_stride + column
+    }
+}
 
-            if transform.position.x < -bounds.x {
-                transform.position.x = -bounds.x;
-                body.velocity.x = body.velocity.x.abs() * body.bounce;
-            } else if transform.position.x > bounds.x {
-                transform.position.x = bounds.x;
-                body.velocity.x = -body.velocity.x.abs() * body.bounce;
-            }
+impl Default for Facing {
+    fn default() -> Self {
+        Self::new(0, 1)
+    }
+}
 
-            if transform.position.y < -bounds.y {
-                transform.position.y = -bounds.y;
-                body.velocity.y = body.velocity.y.abs() * body.bounce;
-            } else if transform.position.y > bounds.y {
-                transform.position.y = bounds.y;
-                body.velocity.y = -body.velocity.y.abs() * body.bounce;
-            }
+pub struct Sprite {
+    pub size: Vec2,
+    pub tile_index: u32,
+    // sRGB tint multiplied with the texture; see `Color`.
+    pub color: Color,
+    pub spin: f32,
+    pub animation: Option<Animation>,
+    pub facing: Option<Facing>,
+    // Lower layers draw first, then lower `order`, then spawn order.
+    pub layer: i32,
+    pub order: i64,
+    // Index of the atlas page in `Assets` this sprite samples from.
+    pub texture_id: u32,
+    // Layer within the page's texture; only array pages have more than one.
+    pub array_layer: u32,
+    // Mirror the tile horizontally/vertically without needing mirrored tiles.
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub blend: BlendMode,
+    // Ids that were never registered draw with the default material.
+    pub material: MaterialId,
+    pub nine_slice: Option<NineSlice>,
+    // UV units per second; the page needs `TextureWrap::Repeat`.
+    pub uv_scroll: Vec2,
+    // Accumulated from `uv_scroll` and kept within 0..1.
+    pub uv_offset: Vec2,
+    // Pivot placed at the position: (0, 0) is the center, (-0.5, 0.5) the top-left.
+    pub anchor: Vec2,
+    // 1.0 moves with the world, 0.0 stays fixed on screen.
+    pub parallax: f32,
+    // Hidden sprites still animate and collide.
+    pub visible: bool,
+    // Runtime effect; not saved in scene files.
+    pub fade: Option<Fade>,
+}
+
+impl Sprite {
+    pub fn new(size: Vec2, tile_index: u32, color: impl Into<Color>) -> Self {
+        Self {
+            size,
+            tile_index,
+            color: color.into(),
+            spin: 0.0,
+            animation: None,
+            facing: None,
+            layer: 0,
+            order: 0,
+            texture_id: 0,
+            array_layer: 0,
+            flip_x: false,
+            flip_y: false,
+            blend: BlendMode::Alpha,
+            material: MaterialId::DEFAULT,
+            nine_slice: None,
+            uv_scroll: Vec2::ZERO,
+            uv_offset: Vec2::ZERO,
+            anchor: Vec2::ZERO,
+            parallax: 1.0,
+            visible: true,
+            fade: None,
         }
     }
 
-    pub fn update_animations(&mut self, dt: f32) {
-        for index in 0..self.transforms.len() {
-            if let (Some(transform), Some(sprite)) = (
-                self.transforms[index].as_mut(),
-                self.sprites[index].as_mut(),
-            ) {
-                if let Some(animation) = sprite.animation.as_mut() {
-                    if let Some(frame) = animation.update(dt) {
-                        sprite.tile_index = frame;
-                    }
-                }
-                transform.rotation += sprite.spin * dt;
-            }
-        }
+    // Offset from the transform position to the middle of the drawn quad.
+    pub fn center_offset(&self, transform: &Transform) -> Vec2 {
+        Vec2::from_angle(transform.rotation).rotate(-self.anchor * self.size * transform.scale)
     }
 
-    pub fn for_each_sprite_world<F: FnMut(Entity, &Transform, &Sprite)>(&mut self, mut f: F) {
-        self.build_world_transforms();
-        for index in 0..self.transforms.len() {
-            if let (Some(world), Some(sprite)) = (
-                self.world_cache[index].as_ref(),
-                self.sprites[index].as_ref(),
-            ) {
-                f(index as Entity, world, sprite);
-            }
-        }
+    // (min, max) corners of the axis-aligned box around the rotated quad.
+    pub fn world_aabb(&self, transform: &Transform) -> (Vec2, Vec2) {
+        let center = transform.position + self.center_offset(transform);
+        let half = (self.size * transform.scale).abs() * 0.5;
+        let (sin, cos) = transform.rotation.sin_cos();
+        let (sin, cos) = (sin.abs(), cos.abs());
+        let extent = Vec2::new(cos * half.x + sin * half.y, sin * half.x + cos * half.y);
+        (center - extent, center + extent)
     }
 
-    fn build_world_transforms(&mut self) {
-        for entry in &mut self.world_cache {
-            *entry = None;
+    // World-space shift applied when drawing.
+    pub fn parallax_offset(&self, camera_position: Vec2) -> Vec2 {
+        camera_position * (1.0 - self.parallax)
+    }
+
+    pub fn set_alpha(&mut self, alpha: f32) {
+        self.color.a = alpha;
+    }
+
+    // Replaces the color channels, keeping the current alpha.
+    pub fn set_tint(&mut self, r: f32, g: f32, b: f32) {
+        self.color = Color::rgba(r, g, b, self.color.a);
+    }
+
+    // `set_tint` taking a `Color`, whose alpha is ignored.
+    pub fn set_tint_color(&mut self, tint: Color) {
+        self.color = tint.with_alpha(self.color.a);
+    }
+
+    // Returns false when the atlas has no clip `name`.
+    pub fn play_clip(&mut self, atlas: &Atlas, name: &str) -> bool {
+        if self.animation.as_ref().and_then(Animation::clip) == Some(name) {
+            return true;
         }
-        let len = self.transforms.len();
-        for index in 0..len {
-            let _ = self.compute_world(index);
+        self.start_clip(atlas, name)
+    }
+
+    fn start_clip(&mut self, atlas: &Atlas, name: &str) -> bool {
+        let Some(animation) = atlas.clip(name) else {
+            log::warn!("Atlas has no animation clip named {:?}", name);
+            return false;
+        };
+        if let Some(&first) = animation.frames().first() {
+            self.tile_index = match &self.facing {
+                Some(facing) => facing.tile(first),
+                None => first,
+            };
         }
+        self.animation = Some(animation);
+        true
     }
+}
 
-    fn compute_world(&mut self, index: usize) -> Option<Transform> {
-        if index >= self.transforms.len() {
-            return None;
+// Uniform grid of buckets for broad-phase queries.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    entries: Vec<(Entity, Vec2, Vec2)>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::new(),
+            entries: Vec::new(),
         }
-        if let Some(cached) = self.world_cache[index] {
-            return Some(cached);
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, min: Vec2, max: Vec2) {
+        let entry = self.entries.len();
+        self.entries.push((entity, min, max));
+        let (cell_min, cell_max) = (self.cell_of(min), self.cell_of(max));
+        for y in cell_min.1..=cell_max.1 {
+            for x in cell_min.0..=cell_max.0 {
+                self.cells.entry((x, y)).or_default().push(entry);
+            }
         }
+    }
 
-        let local = self.transforms[index]?;
-        let world = match self.parents.get(index).and_then(|parent| *parent) {
-            Some(parent) => {
-                let parent_index = parent as usize;
-                if parent_index == index {
-                    local
-                } else if let Some(parent_world) = self.compute_world(parent_index) {
-                    combine_transforms(parent_world, local)
-                } else {
-                    local
+    pub fn query_aabb(&self, min: Vec2, max: Vec2) -> Vec<Entity> {
+        let (cell_min, cell_max) = (self.cell_of(min), self.cell_of(max));
+        let mut candidates = Vec::new();
+        for y in cell_min.1..=cell_max.1 {
+            for x in cell_min.0..=cell_max.0 {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    candidates.extend_from_slice(cell);
                 }
             }
-            None => local,
-        };
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+            .into_iter()
+            .map(|entry| self.entries[entry])
+            .filter(|&(_, entry_min, entry_max)| {
+                entry_min.cmple(max).all() && entry_max.cmpge(min).all()
+            })
+            .map(|(entity, _, _)| entity)
+            .collect()
+    }
 
-        self.world_cache[index] = Some(world);
-        Some(world)
+    pub fn query_point(&self, point: Vec2) -> Vec<Entity> {
+        self.query_aabb(point, point)
     }
 
-    fn push_new(&mut self, transform: Transform, sprite: Sprite, body: Option<Body>) -> Entity {
-        let entity = self.transforms.len() as Entity;
-        self.transforms.push(Some(transform));
-        self.sprites.push(Some(sprite));
-        self.bodies.push(body);
-        self.parents.push(None);
-        self.world_cache.push(None);
-        entity
+    fn cell_of(&self, point: Vec2) -> (i32, i32) {
+        let cell = (point / self.cell_size).floor();
+        (cell.x as i32, cell.y as i32)
+    }
+}
+
+// Entities are referenced by their position in `entities`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SceneFile {
+    pub entities: Vec<EntityDesc>,
+}
+
+impl SceneFile {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Self::from_json(&contents)?)
+    }
+
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json()?)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EntityDesc {
+    #[serde(default)]
+    pub transform: Transform,
+    pub sprite: SpriteDesc,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Body>,
+    #[serde(default, skip_serializing_if = "Collider::is_aabb")]
+    pub collider: Collider,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_trigger: bool,
+    #[serde(default, skip_serializing_if = "CollisionLayers::is_default")]
+    pub collision_layers: CollisionLayers,
+    // Index into `SceneFile::entities`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpriteDesc {
+    pub size: Vec2,
+    pub tile_index: u32,
+    pub color: Color,
+    pub spin: f32,
+    pub layer: i32,
+    pub order: i64,
+    pub texture_id: u32,
+    pub array_layer: u32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub blend: BlendMode,
+    pub material: MaterialId,
+    pub anchor: Vec2,
+    pub parallax: f32,
+    pub visible: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub animation: Option<AnimationDesc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facing: Option<Facing>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nine_slice: Option<NineSlice>,
+    pub uv_scroll: Vec2,
+}
+
+impl Default for SpriteDesc {
+    fn default() -> Self {
+        Self::from_sprite(&Sprite::new(Vec2::ONE, 0, [1.0; 4]))
+    }
+}
+
+impl SpriteDesc {
+    fn from_sprite(sprite: &Sprite) -> Self {
+        Self {
+            size: sprite.size,
+            tile_index: sprite.tile_index,
+            color: sprite.color,
+            spin: sprite.spin,
+            layer: sprite.layer,
+            order: sprite.order,
+            texture_id: sprite.texture_id,
+            array_layer: sprite.array_layer,
+            flip_x: sprite.flip_x,
+            flip_y: sprite.flip_y,
+            blend: sprite.blend,
+            material: sprite.material,
+            anchor: sprite.anchor,
+            parallax: sprite.parallax,
+            visible: sprite.visible,
+            animation: sprite.animation.as_ref().map(|animation| AnimationDesc {
+                frames: animation.frames().to_vec(),
+                fps: animation.fps(),
+                mode: animation.mode(),
+                pausable: animation.pausable(),
+            }),
+            facing: sprite.facing,
+            nine_slice: sprite.nine_slice,
+            uv_scroll: sprite.uv_scroll,
+        }
+    }
+
+    fn to_sprite(&self) -> Sprite {
+        Sprite {
+            spin: self.spin,
+            layer: self.layer,
+            order: self.order,
+            texture_id: self.texture_id,
+            array_layer: self.array_layer,
+            flip_x: self.flip_x,
+            flip_y: self.flip_y,
+            blend: self.blend,
+            material: self.material,
+            anchor: self.anchor,
+            parallax: self.parallax,
+            visible: self.visible,
+            animation: self.animation.as_ref().map(AnimationDesc::to_animation),
+            facing: self.facing,
+            nine_slice: self.nine_slice,
+            uv_scroll: self.uv_scroll,
+            ..Sprite::new(self.size, self.tile_index, self.color)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationDesc {
+    pub frames: Vec<u32>,
+    pub fps: f32,
+    #[serde(default)]
+    pub mode: PlaybackMode,
+    // Only written when false, which keeps existing scene files unchanged.
+    #[serde(default = "pausable_by_default", skip_serializing_if = "is_pausable")]
+    pub pausable: bool,
+}
+
+impl AnimationDesc {
+    pub fn to_animation(&self) -> Animation {
+        Animation::new(self.frames.clone(), self.fps)
+            .with_mode(self.mode)
+            .with_pausable(self.pausable)
+    }
+}
+
+fn pausable_by_default() -> bool {
+    true
+}
+
+fn is_pausable(pausable: &bool) -> bool {
+    *pausable
+}
+
+// Index of a tilemap in its `World`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TileMapId(pub u32);
+
+pub struct World {
+    transforms: Vec<Option<Transform>>,
+    sprites: Vec<Option<Sprite>>,
+    bodies: Vec<Option<Body>>,
+    colliders: Vec<Collider>,
+    triggers: Vec<bool>,
+    collision_layers: Vec<CollisionLayers>,
+    parents: Vec<Option<Entity>>,
+    previous: Vec<Option<Transform>>,
+    world_cache: Vec<Option<Transform>>,
+    child_cache: Vec<Vec<usize>>,
+    generations: Vec<u32>,
+    spawn_order: Vec<u64>,
+    next_spawn: u64,
+    free: Vec<u32>,
+    gravity: Vec2,
+    max_substeps: u32,
+    trigger_overlaps: BTreeSet<(Entity, Entity)>,
+    trigger_events: Vec<TriggerEvent>,
+    tilemaps: Vec<TileMap>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self {
+            transforms: Vec::new(),
+            sprites: Vec::new(),
+            bodies: Vec::new(),
+            colliders: Vec::new(),
+            triggers: Vec::new(),
+            collision_layers: Vec::new(),
+            parents: Vec::new(),
+            previous: Vec::new(),
+            world_cache: Vec::new(),
+            child_cache: Vec::new(),
+            generations: Vec::new(),
+            spawn_order: Vec::new(),
+            next_spawn: 0,
+            free: Vec::new(),
+            gravity: Vec2::ZERO,
+            max_substeps: 1,
+            trigger_overlaps: BTreeSet::new(),
+            trigger_events: Vec::new(),
+            tilemaps: Vec::new(),
+        }
+    }
+
+    pub fn spawn_tilemap(&mut self, tilemap: TileMap) -> TileMapId {
+        self.tilemaps.push(tilemap);
+        TileMapId(self.tilemaps.len() as u32 - 1)
+    }
+
+    pub fn tilemap(&self, id: TileMapId) -> Option<&TileMap> {
+        self.tilemaps.get(id.0 as usize)
+    }
+
+    pub fn tilemap_mut(&mut self, id: TileMapId) -> Option<&mut TileMap> {
+        self.tilemaps.get_mut(id.0 as usize)
+    }
+
+    pub fn tilemaps(&self) -> &[TileMap] {
+        &self.tilemaps
+    }
+
+    // Increases with every spawn and is never reused.
+    pub fn spawn_order(&self, entity: Entity) -> Option<u64> {
+        self.is_alive(entity)
+            .then(|| self.spawn_order[entity.index as usize])
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.generations.get(entity.index as usize) == Some(&entity.generation)
+            && self.transforms[entity.index as usize].is_some()
+    }
+
+    // Starts an `EntityBuilder`; nothing is spawned until its `spawn`.
+    pub fn build(&mut self) -> EntityBuilder<'_> {
+        EntityBuilder::new(self)
+    }
+
+    pub fn spawn_sprite(&mut self, transform: Transform, sprite: Sprite) -> Entity {
+        self.spawn_sprite_with_body(transform, sprite, None)
+    }
+
+    pub fn spawn_sprite_with_body(
+        &mut self,
+        transform: Transform,
+        sprite: Sprite,
+        body: Option<Body>,
+    ) -> Entity {
+        if let Some(slot) = self.free.pop() {
+            let index = slot as usize;
+            if index >= self.transforms.len() {
+                return self.push_new(transform, sprite, body);
+            }
+            self.transforms[index] = Some(transform);
+            self.sprites[index] = Some(sprite);
+            self.bodies[index] = body;
+            self.colliders[index] = Collider::Aabb;
+            self.triggers[index] = false;
+            self.collision_layers[index] = CollisionLayers::default();
+            self.parents[index] = None;
+            self.previous[index] = None;
+            self.world_cache[index] = None;
+            self.spawn_order[index] = self.next_spawn;
+            self.next_spawn += 1;
+            Entity {
+                index: slot,
+                generation: self.generations[index],
+            }
+        } else {
+            self.push_new(transform, sprite, body)
+        }
+    }
+
+    // Returns false for handles that were already stale.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        let index = entity.index as usize;
+        self.transforms[index] = None;
+        self.sprites[index] = None;
+        self.bodies[index] = None;
+        self.colliders[index] = Collider::Aabb;
+        self.triggers[index] = false;
+        self.collision_layers[index] = CollisionLayers::default();
+        self.parents[index] = None;
+        self.previous[index] = None;
+        self.world_cache[index] = None;
+        self.generations[index] = self.generations[index].wrapping_add(1);
+        self.free.push(entity.index);
+        true
+    }
+
+    // Returns the new handles in the same order as `scene.entities`.
+    pub fn load_scene(&mut self, scene: &SceneFile) -> Vec<Entity> {
+        let spawned: Vec<Entity> = scene
+            .entities
+            .iter()
+            .map(|desc| {
+                let entity =
+                    self.spawn_sprite_with_body(desc.transform, desc.sprite.to_sprite(), desc.body);
+                self.set_collider(entity, desc.collider);
+                self.set_trigger(entity, desc.is_trigger);
+                self.set_collision_layers(entity, desc.collision_layers);
+                entity
+            })
+            .collect();
+        for (desc, &entity) in scene.entities.iter().zip(&spawned) {
+            match desc.parent.and_then(|parent| spawned.get(parent)) {
+                Some(&parent) => {
+                    self.link_parent(entity, parent);
+                }
+                None if desc.parent.is_some() => {
+                    log::warn!("Scene entity has out-of-range parent {:?}", desc.parent)
+                }
+                None => {}
+            }
+        }
+        self.world_cache.fill(None);
+        spawned
+    }
+
+    // Live entities are written in slot order with local transforms.
+    pub fn save_scene(&self) -> SceneFile {
+        let mut file_index = vec![None; self.transforms.len()];
+        let mut live = Vec::new();
+        for index in self.indices_in_spawn_order() {
+            if let (Some(transform), Some(sprite)) = (self.transforms[index], &self.sprites[index])
+            {
+                file_index[index] = Some(live.len());
+                live.push((index, transform, sprite));
+            }
+        }
+        let entities = live
+            .into_iter()
+            .map(|(index, transform, sprite)| EntityDesc {
+                transform,
+                sprite: SpriteDesc::from_sprite(sprite),
+                body: self.bodies[index],
+                collider: self.colliders[index],
+                is_trigger: self.triggers[index],
+                collision_layers: self.collision_layers[index],
+                parent: self.parents[index]
+                    .filter(|parent| self.is_alive(*parent))
+                    .and_then(|parent| file_index[parent.index as usize]),
+            })
+            .collect();
+        SceneFile { entities }
+    }
+
+    // Refused when `parent` is `child` or one of its descendants.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> bool {
+        let linked = self.link_parent(child, parent);
+        if linked {
+            self.invalidate_subtree(child);
+        }
+        linked
+    }
+
+    fn link_parent(&mut self, child: Entity, parent: Entity) -> bool {
+        if !self.is_alive(child) {
+            return false;
+        }
+        if self.is_ancestor_or_self(child, parent) {
+            log::warn!(
+                "Refusing to parent {:?} under {:?}: would form a cycle",
+                child,
+                parent
+            );
+            return false;
+        }
+        self.parents[child.index as usize] = Some(parent);
+        true
+    }
+
+    // Detaches `child`, keeping its local transform as its new world transform.
+    pub fn clear_parent(&mut self, child: Entity) {
+        if !self.is_alive(child) {
+            return;
+        }
+        self.parents[child.index as usize] = None;
+        self.invalidate_subtree(child);
+    }
+
+    pub fn parent(&self, child: Entity) -> Option<Entity> {
+        if !self.is_alive(child) {
+            return None;
+        }
+        self.parents[child.index as usize].filter(|parent| self.is_alive(*parent))
+    }
+
+    fn is_ancestor_or_self(&self, ancestor: Entity, entity: Entity) -> bool {
+        let mut current = Some(entity);
+        for _ in 0..=self.transforms.len() {
+            match current {
+                Some(node) if node == ancestor => return true,
+                Some(node) => current = self.parent(node),
+                None => return false,
+            }
+        }
+        false
+    }
+
+    fn invalidate_subtree(&mut self, root: Entity) {
+        for index in 0..self.transforms.len() {
+            let entity = self.entity_at(index);
+            if self.is_alive(entity) && self.is_ancestor_or_self(root, entity) {
+                self.world_cache[index] = None;
+            }
+        }
+    }
+
+    pub fn collider(&self, entity: Entity) -> Option<Collider> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        Some(self.colliders[entity.index as usize])
+    }
+
+    pub fn set_collider(&mut self, entity: Entity, collider: Collider) {
+        if self.is_alive(entity) {
+            self.colliders[entity.index as usize] = collider;
+        }
+    }
+
+    pub fn is_trigger(&self, entity: Entity) -> bool {
+        self.is_alive(entity) && self.triggers[entity.index as usize]
+    }
+
+    pub fn set_trigger(&mut self, entity: Entity, is_trigger: bool) {
+        if self.is_alive(entity) {
+            self.triggers[entity.index as usize] = is_trigger;
+        }
+    }
+
+    pub fn collision_layers(&self, entity: Entity) -> Option<CollisionLayers> {
+        self.is_alive(entity)
+            .then(|| self.collision_layers[entity.index as usize])
+    }
+
+    pub fn set_collision_layers(&mut self, entity: Entity, layers: CollisionLayers) {
+        if self.is_alive(entity) {
+            self.collision_layers[entity.index as usize] = layers;
+        }
+    }
+
+    // Live entities on any of the layers in `mask`, in slot order.
+    pub fn iter_with_tag(&self, mask: u32) -> impl Iterator<Item = Entity> + '_ {
+        (0..self.transforms.len())
+            .map(|index| self.entity_at(index))
+            .filter(move |&entity| {
+                self.is_alive(entity)
+                    && self.collision_layers[entity.index as usize].layers & mask != 0
+            })
+    }
+
+    // Hides or shows the entity's sprite without despawning it.
+    pub fn set_visible(&mut self, entity: Entity, visible: bool) {
+        if let Some(sprite) = self.get_sprite_mut(entity) {
+            sprite.visible = visible;
+        }
+    }
+
+    // Pauses or resumes the entity's body in `step_physics`.
+    pub fn set_body_active(&mut self, entity: Entity, active: bool) {
+        if self.is_alive(entity)
+            && let Some(body) = self.bodies[entity.index as usize].as_mut()
+        {
+            body.active = active;
+        }
+    }
+
+    // Enter/Exit events from the most recent `step_collisions`.
+    pub fn trigger_events(&self) -> &[TriggerEvent] {
+        &self.trigger_events
+    }
+
+    pub fn get_transform_mut(&mut self, entity: Entity) -> Option<&mut Transform> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.transforms[entity.index as usize].as_mut()
+    }
+
+    pub fn get_sprite_mut(&mut self, entity: Entity) -> Option<&mut Sprite> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.sprites[entity.index as usize].as_mut()
+    }
+
+    // Walks the parent chain instead of using the per-frame cache.
+    pub fn world_transform(&self, entity: Entity) -> Option<Transform> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        let mut world = self.transforms[entity.index as usize]?;
+        let mut current = entity;
+        for _ in 0..self.transforms.len() {
+            let Some(parent) = self.parents[current.index as usize] else {
+                break;
+            };
+            if !self.is_alive(parent) || parent == current {
+                break;
+            }
+            let Some(parent_local) = self.transforms[parent.index as usize] else {
+                break;
+            };
+            world = combine_transforms(parent_local, world);
+            current = parent;
+        }
+        Some(world)
+    }
+
+    // Rotation-aware bounds of the entity's sprite in world space.
+    pub fn world_aabb(&self, entity: Entity) -> Option<(Vec2, Vec2)> {
+        let transform = self.world_transform(entity)?;
+        let sprite = self.sprites[entity.index as usize].as_ref()?;
+        Some(sprite.world_aabb(&transform))
+    }
+
+    // (min, max) corners of the box the collision step uses.
+    pub fn collision_bounds(&self, entity: Entity) -> Option<(Vec2, Vec2)> {
+        let transform = self.world_transform(entity)?;
+        let sprite = self.sprites[entity.index as usize].as_ref()?;
+        let (center, half) =
+            Shape::new(self.colliders[entity.index as usize], sprite, &transform).bounds();
+        Some((center - half, center + half))
+    }
+
+    pub fn gravity(&self) -> Vec2 {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec2) {
+        self.gravity = gravity;
+    }
+
+    pub fn set_max_substeps(&mut self, max_substeps: u32) {
+        self.max_substeps = max_substeps.max(1);
+    }
+
+    // Keeps each body's move per sub-step under half the smallest collider.
+    pub fn substep_count(&mut self, dt: f32) -> u32 {
+        if self.max_substeps <= 1 {
+            return 1;
+        }
+        self.build_world_transforms(1.0);
+        let smallest = (0..self.transforms.len())
+            .filter_map(|index| self.collision_box(index))
+            .map(|(_, half)| half.min_element() * 2.0)
+            .filter(|&size| size > 0.0)
+            .fold(f32::INFINITY, f32::min);
+        let fastest = self
+            .bodies
+            .iter()
+            .flatten()
+            .filter(|body| body.active)
+            .map(|body| body.velocity.length())
+            .fold(0.0, f32::max);
+        if !smallest.is_finite() || fastest <= 0.0 {
+            return 1;
+        }
+        let needed = (fastest * dt / (smallest * 0.5)).ceil();
+        (needed as u32).clamp(1, self.max_substeps)
+    }
+
+    // Runs physics and collisions over equal slices of `dt`.
+    pub fn step_physics_substepped(
+        &mut self,
+        dt: f32,
+        bounds: Vec2,
+    ) -> (Vec<CollisionEvent>, Vec<Entity>) {
+        let substeps = self.substep_count(dt);
+        let sub_dt = dt / substeps as f32;
+        let mut events = Vec::new();
+        let mut trigger_events = Vec::new();
+        let mut destroyed = Vec::new();
+        for _ in 0..substeps {
+            let newly_destroyed = self.integrate_bodies(sub_dt, bounds, &destroyed);
+            destroyed.extend(newly_destroyed);
+            for event in self.resolve_overlaps(&destroyed) {
+                if !events.contains(&event) {
+                    events.push(event);
+                }
+            }
+            trigger_events.append(&mut self.trigger_events);
+        }
+        self.trigger_events = trigger_events;
+        (events, destroyed)
+    }
+
+    // Returns the `BoundsBehavior::Destroy` entities that left the bounds.
+    pub fn step_physics(&mut self, dt: f32, bounds: Vec2) -> Vec<Entity> {
+        self.integrate_bodies(dt, bounds, &[])
+    }
+
+    fn integrate_bodies(&mut self, dt: f32, bounds: Vec2, skipped: &[Entity]) -> Vec<Entity> {
+        let mut destroyed = Vec::new();
+        for index in 0..self.transforms.len() {
+            if skipped.contains(&self.entity_at(index)) {
+                continue;
+            }
+            let (Some(transform), Some(body)) =
+                (self.transforms[index].as_mut(), self.bodies[index].as_mut())
+            else {
+                continue;
+            };
+            if !body.active {
+                continue;
+            }
+
+            if !body.is_kinematic() {
+                if body.affected_by_gravity() {
+                    body.velocity += self.gravity * dt;
+                }
+                let damping = (1.0 - body.damping * dt).clamp(0.0, 1.0);
+                body.velocity *= damping;
+            }
+            transform.position += body.velocity * dt;
+
+            let position = transform.position;
+            match body.bounds_behavior {
+                _ if position.abs().cmple(bounds).all() => {}
+                BoundsBehavior::Bounce if !body.is_kinematic() => {
+                    if position.x < -bounds.x {
+                        transform.position.x = -bounds.x;
+                        body.velocity.x = body.velocity.x.abs() * body.bounce;
+                    } else if position.x > bounds.x {
+                        transform.position.x = bounds.x;
+                        body.velocity.x = -body.velocity.x.abs() * body.bounce;
+                    }
+
+                    if position.y < -bounds.y {
+                        transform.position.y = -bounds.y;
+                        body.velocity.y = body.velocity.y.abs() * body.bounce;
+                    } else if position.y > bounds.y {
+                        transform.position.y = bounds.y;
+                        body.velocity.y = -body.velocity.y.abs() * body.bounce;
+                    }
+                }
+                BoundsBehavior::Bounce | BoundsBehavior::Clamp => {
+                    transform.position = position.clamp(-bounds, bounds);
+                    if transform.position.x != position.x {
+                        body.velocity.x = 0.0;
+                    }
+                    if transform.position.y != position.y {
+                        body.velocity.y = 0.0;
+                    }
+                }
+                BoundsBehavior::Wrap => {
+                    transform.position = wrap_position(position, bounds);
+                    if let Some(previous) = self.previous[index].as_mut() {
+                        previous.position += transform.position - position;
+                    }
+                }
+                BoundsBehavior::Destroy => destroyed.push(Entity {
+                    index: index as u32,
+                    generation: self.generations[index],
+                }),
+            }
+
+            if !body.is_kinematic() {
+                body.clamp_speed();
+            }
+            if let Some(angle) = body.facing_angle() {
+                let turn = (angle - transform.rotation + std::f32::consts::PI)
+                    .rem_euclid(std::f32::consts::TAU)
+                    - std::f32::consts::PI;
+                transform.rotation += turn;
+            }
+        }
+        destroyed
+    }
+
+    // Despawns bodies outside the half extents `bounds` around the origin.
+    pub fn despawn_outside(&mut self, bounds: Vec2) -> Vec<Entity> {
+        let outside: Vec<Entity> = (0..self.transforms.len())
+            .map(|index| self.entity_at(index))
+            .filter(|&entity| {
+                self.bodies[entity.index as usize].is_some()
+                    && self
+                        .world_transform(entity)
+                        .is_some_and(|transform| transform.position.abs().cmpgt(bounds).any())
+            })
+            .collect();
+        for &entity in &outside {
+            self.despawn(entity);
+        }
+        outside
+    }
+
+    // Buckets the bounding box of every collision shape.
+    pub fn build_spatial_hash(&mut self, cell_size: f32) -> SpatialHash {
+        self.build_world_transforms(1.0);
+        self.spatial_hash_from_cache(cell_size)
+    }
+
+    fn spatial_hash_from_cache(&self, cell_size: f32) -> SpatialHash {
+        let mut hash = SpatialHash::new(cell_size);
+        for index in 0..self.transforms.len() {
+            if let Some((center, half)) = self.collision_box(index) {
+                hash.insert(self.entity_at(index), center - half, center + half);
+            }
+        }
+        hash
+    }
+
+    fn typical_cell_size(&self) -> f32 {
+        let (total, count) = (0..self.transforms.len())
+            .filter_map(|index| self.collision_box(index))
+            .fold((0.0, 0), |(total, count), (_, half)| {
+                (total + half.max_element() * 2.0, count + 1)
+            });
+        if count == 0 {
+            1.0
+        } else {
+            total / count as f32
+        }
+    }
+
+    // Resolves overlaps; at least one side of a pair must have a body.
+    pub fn step_collisions(&mut self) -> Vec<CollisionEvent> {
+        self.resolve_overlaps(&[])
+    }
+
+    fn resolve_overlaps(&mut self, skipped: &[Entity]) -> Vec<CollisionEvent> {
+        self.build_world_transforms(1.0);
+        let cell_size = self.typical_cell_size();
+        let hash = self.spatial_hash_from_cache(cell_size);
+        let mut events = Vec::new();
+        let mut overlaps = BTreeSet::new();
+        for a in 0..self.transforms.len() {
+            if skipped.contains(&self.entity_at(a)) {
+                continue;
+            }
+            let Some((center, half)) = self.collision_box(a) else {
+                continue;
+            };
+            for other in hash.query_aabb(center - half, center + half) {
+                let b = other.index as usize;
+                if b <= a || skipped.contains(&other) {
+                    continue;
+                }
+                if self.bodies[a].is_none() && self.bodies[b].is_none() {
+                    continue;
+                }
+                if !self.collision_layers[a].interacts_with(self.collision_layers[b]) {
+                    continue;
+                }
+                let (Some(shape_a), Some(shape_b)) =
+                    (self.collision_shape(a), self.collision_shape(b))
+                else {
+                    continue;
+                };
+                let Some(normal) = shape_penetration(shape_a, shape_b) else {
+                    continue;
+                };
+                if self.triggers[a] || self.triggers[b] {
+                    let (entity_a, entity_b) = (self.entity_at(a), self.entity_at(b));
+                    if self.triggers[a] {
+                        overlaps.insert((entity_a, entity_b));
+                    }
+                    if self.triggers[b] {
+                        overlaps.insert((entity_b, entity_a));
+                    }
+                    continue;
+                }
+                self.resolve_collision(a, b, normal);
+                events.push(CollisionEvent {
+                    a: self.entity_at(a),
+                    b: self.entity_at(b),
+                });
+            }
+        }
+        self.update_trigger_events(overlaps);
+        events
+    }
+
+    fn update_trigger_events(&mut self, overlaps: BTreeSet<(Entity, Entity)>) {
+        self.trigger_events.clear();
+        for &(trigger, other) in overlaps.difference(&self.trigger_overlaps) {
+            self.trigger_events.push(TriggerEvent {
+                trigger,
+                other,
+                phase: TriggerPhase::Enter,
+            });
+        }
+        for &(trigger, other) in self.trigger_overlaps.difference(&overlaps) {
+            self.trigger_events.push(TriggerEvent {
+                trigger,
+                other,
+                phase: TriggerPhase::Exit,
+            });
+        }
+        self.trigger_overlaps = overlaps;
+    }
+
+    // Rebuilds clip animations from the current atlas config.
+    pub fn refresh_clips(&mut self, assets: &Assets) {
+        for sprite in self.sprites.iter_mut().flatten() {
+            let Some(name) = sprite.animation.as_ref().and_then(|a| a.clip.clone()) else {
+                continue;
+            };
+            sprite.start_clip(assets.atlas(sprite.texture_id), &name);
+        }
+    }
+
+    // While `paused`, only non-pausable animations advance.
+    pub fn update_animations(&mut self, dt: f32, paused: bool) -> Vec<(Entity, AnimationEvent)> {
+        let mut events = Vec::new();
+        for index in 0..self.transforms.len() {
+            let entity = self.entity_at(index);
+            if let (Some(transform), Some(sprite)) = (
+                self.transforms[index].as_mut(),
+                self.sprites[index].as_mut(),
+            ) {
+                let mut frame = None;
+                if let Some(animation) = sprite.animation.as_mut()
+                    && !(paused && animation.pausable)
+                {
+                    let was_finished = animation.finished();
+                    frame = animation.update(dt);
+                    if animation.finished() && !was_finished {
+                        events.push((entity, AnimationEvent::Finished));
+                    }
+                }
+                if paused {
+                    if let Some(frame) = frame {
+                        sprite.tile_index = match &sprite.facing {
+                            Some(facing) => facing.tile(frame),
+                            None => frame,
+                        };
+                    }
+                    continue;
+                }
+                if let Some(facing) = sprite.facing.as_mut() {
+                    if let Some(body) = &self.bodies[index] {
+                        facing.update(body.velocity);
+                    }
+                    sprite.tile_index = facing.tile(frame.unwrap_or(0));
+                } else if let Some(frame) = frame {
+                    sprite.tile_index = frame;
+                }
+                if let Some(fade) = sprite.fade.as_mut() {
+                    let alpha = fade.update(dt);
+                    let finished = fade.finished();
+                    sprite.set_alpha(alpha);
+                    if finished {
+                        sprite.fade = None;
+                    }
+                }
+                if sprite.uv_scroll != Vec2::ZERO {
+                    let offset = sprite.uv_offset + sprite.uv_scroll * dt;
+                    sprite.uv_offset = offset.rem_euclid(Vec2::ONE);
+                }
+                let faces_velocity = self.bodies[index].is_some_and(|body| body.face_velocity);
+                if !faces_velocity {
+                    transform.rotation += sprite.spin * dt;
+                }
+            }
+        }
+        events
+    }
+
+    // Call before each fixed step.
+    pub fn store_previous_transforms(&mut self) {
+        self.previous.clone_from(&self.transforms);
+    }
+
+    // Live entities with a body, in index order.
+    pub fn iter_bodies_mut(&mut self) -> impl Iterator<Item = (Entity, &mut Transform, &mut Body)> {
+        let generations = &self.generations;
+        self.transforms
+            .iter_mut()
+            .zip(self.bodies.iter_mut())
+            .enumerate()
+            .filter_map(move |(index, (transform, body))| {
+                let entity = Entity {
+                    index: index as u32,
+                    generation: generations[index],
+                };
+                Some((entity, transform.as_mut()?, body.as_mut()?))
+            })
+    }
+
+    // Live entities with a sprite, in index order.
+    pub fn iter_sprites(&self) -> impl Iterator<Item = (Entity, &Transform, &Sprite)> {
+        self.transforms
+            .iter()
+            .zip(self.sprites.iter())
+            .enumerate()
+            .filter_map(|(index, (transform, sprite))| {
+                Some((self.entity_at(index), transform.as_ref()?, sprite.as_ref()?))
+            })
+    }
+
+    // `alpha` blends from the previous snapshot (0.0) to the current transform (1.0).
+    pub fn for_each_sprite_world<F: FnMut(Entity, &Transform, &Sprite)>(
+        &mut self,
+        alpha: f32,
+        mut f: F,
+    ) {
+        self.build_world_transforms(alpha);
+        for index in 0..self.transforms.len() {
+            if let (Some(world), Some(sprite)) = (
+                self.world_cache[index].as_ref(),
+                self.sprites[index].as_ref(),
+            ) && sprite.visible
+            {
+                f(self.entity_at(index), world, sprite);
+            }
+        }
+    }
+
+    // Like `for_each_sprite_world`, in spawn order.
+    pub fn for_each_sprite_world_ordered<F: FnMut(Entity, &Transform, &Sprite)>(
+        &mut self,
+        alpha: f32,
+        mut f: F,
+    ) {
+        self.build_world_transforms(alpha);
+        for index in self.indices_in_spawn_order() {
+            if let (Some(world), Some(sprite)) = (
+                self.world_cache[index].as_ref(),
+                self.sprites[index].as_ref(),
+            ) && sprite.visible
+            {
+                f(self.entity_at(index), world, sprite);
+            }
+        }
+    }
+
+    fn build_world_transforms(&mut self, alpha: f32) {
+        for entry in &mut self.world_cache {
+            *entry = None;
+        }
+        let len = self.transforms.len();
+        self.child_cache.resize_with(len, Vec::new);
+        for children in &mut self.child_cache {
+            children.clear();
+        }
+        for index in 0..len {
+            let _ = self.compute_world(index, alpha, 0);
+            if self.transforms[index].is_some()
+                && let Some(parent) = self.parents[index].filter(|parent| self.is_alive(*parent))
+                && parent.index as usize != index
+            {
+                self.child_cache[parent.index as usize].push(index);
+            }
+        }
+    }
+
+    fn interpolated_local(&self, index: usize, alpha: f32) -> Option<Transform> {
+        let current = self.transforms[index]?;
+        if alpha >= 1.0 {
+            return Some(current);
+        }
+        Some(match self.previous.get(index).copied().flatten() {
+            Some(previous) => lerp_transforms(previous, current, alpha),
+            None => current,
+        })
+    }
+
+    fn compute_world(&mut self, index: usize, alpha: f32, depth: usize) -> Option<Transform> {
+        if index >= self.transforms.len() {
+            return None;
+        }
+        if let Some(cached) = self.world_cache[index] {
+            return Some(cached);
+        }
+
+        let local = self.interpolated_local(index, alpha)?;
+        let parent = self.parents[index].filter(|parent| self.is_alive(*parent));
+        let world = match parent {
+            Some(parent) => {
+                let parent_index = parent.index as usize;
+                if parent_index == index || depth >= self.transforms.len() {
+                    local
+                } else if let Some(parent_world) =
+                    self.compute_world(parent_index, alpha, depth + 1)
+                {
+                    combine_transforms(parent_world, local)
+                } else {
+                    local
+                }
+            }
+            None => local,
+        };
+
+        self.world_cache[index] = Some(world);
+        Some(world)
+    }
+
+    fn push_new(&mut self, transform: Transform, sprite: Sprite, body: Option<Body>) -> Entity {
+        let entity = Entity {
+            index: self.transforms.len() as u32,
+            generation: 0,
+        };
+        self.transforms.push(Some(transform));
+        self.sprites.push(Some(sprite));
+        self.bodies.push(body);
+        self.colliders.push(Collider::Aabb);
+        self.triggers.push(false);
+        self.collision_layers.push(CollisionLayers::default());
+        self.parents.push(None);
+        self.previous.push(None);
+        self.world_cache.push(None);
+        self.generations.push(0);
+        self.spawn_order.push(self.next_spawn);
+        self.next_spawn += 1;
+        entity
+    }
+
+    fn indices_in_spawn_order(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.transforms.len())
+            .filter(|&index| self.transforms[index].is_some())
+            .collect();
+        indices.sort_by_key(|&index| self.spawn_order[index]);
+        indices
+    }
+
+    fn collision_shape(&self, index: usize) -> Option<Shape> {
+        let transform = self.world_cache[index].as_ref()?;
+        let sprite = self.sprites[index].as_ref()?;
+        Some(Shape::new(self.colliders[index], sprite, transform))
+    }
+
+    fn collision_box(&self, index: usize) -> Option<(Vec2, Vec2)> {
+        self.collision_shape(index).map(|shape| shape.bounds())
+    }
+
+    fn resolve_collision(&mut self, a: usize, b: usize, normal: Vec2) {
+        let direction = normal.normalize_or_zero();
+        let dynamic = |index: usize| self.bodies[index].filter(Body::is_pushable);
+        let surface_velocity = |index: usize| {
+            self.bodies[index]
+                .filter(|body| body.active)
+                .map_or(Vec2::ZERO, |body| body.velocity)
+        };
+        let (velocity_a, velocity_b) = (surface_velocity(a), surface_velocity(b));
+        match (dynamic(a), dynamic(b)) {
+            (Some(mut body_a), Some(mut body_b)) => {
+                let (inverse_a, inverse_b) = (body_a.inverse_mass(), body_b.inverse_mass());
+                let share_a = inverse_a / (inverse_a + inverse_b);
+                self.offset_position(a, -normal * share_a);
+                self.offset_position(b, normal * (1.0 - share_a));
+                (body_a.velocity, body_b.velocity) = contact_velocities(
+                    (body_a.velocity, body_a.inverse_mass()),
+                    (body_b.velocity, body_b.inverse_mass()),
+                    direction,
+                    (body_a.bounce * body_b.bounce).max(0.0).sqrt(),
+                    (body_a.friction * body_b.friction).max(0.0).sqrt(),
+                );
+                self.bodies[a] = Some(body_a);
+                self.bodies[b] = Some(body_b);
+            }
+            (Some(mut body), None) => {
+                self.offset_position(a, -normal);
+                (body.velocity, _) = contact_velocities(
+                    (body.velocity, body.inverse_mass()),
+                    (velocity_b, 0.0),
+                    direction,
+                    body.bounce,
+                    body.friction,
+                );
+                self.bodies[a] = Some(body);
+            }
+            (None, Some(mut body)) => {
+                self.offset_position(b, normal);
+                (_, body.velocity) = contact_velocities(
+                    (velocity_a, 0.0),
+                    (body.velocity, body.inverse_mass()),
+                    direction,
+                    body.bounce,
+                    body.friction,
+                );
+                self.bodies[b] = Some(body);
+            }
+            (None, None) => {}
+        }
+    }
+
+    fn offset_position(&mut self, index: usize, offset: Vec2) {
+        let entity = self.entity_at(index);
+        let parent_world = self
+            .parent(entity)
+            .and_then(|parent| self.world_cache[parent.index as usize]);
+        let local_offset = match parent_world {
+            Some(parent) => {
+                let rotated = rotate_vec2(offset, -parent.rotation);
+                Vec2::select(
+                    parent.scale.cmpeq(Vec2::ZERO),
+                    Vec2::ZERO,
+                    rotated / parent.scale,
+                )
+            }
+            None => offset,
+        };
+        if let Some(transform) = self.transforms[index].as_mut() {
+            transform.position += local_offset;
+        }
+        let mut pending = vec![index];
+        for _ in 0..self.transforms.len() {
+            let Some(node) = pending.pop() else {
+                break;
+            };
+            if let Some(world) = self.world_cache[node].as_mut() {
+                world.position += offset;
+            }
+            if let Some(children) = self.child_cache.get(node) {
+                pending.extend_from_slice(children);
+            }
+        }
+    }
+
+    fn entity_at(&self, index: usize) -> Entity {
+        Entity {
+            index: index as u32,
+            generation: self.generations[index],
+        }
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Collects every component of an entity and spawns it in one go.
+pub struct EntityBuilder<'w> {
+    world: &'w mut World,
+    transform: Transform,
+    sprite: Option<Sprite>,
+    body: Option<Body>,
+    collider: Collider,
+    is_trigger: bool,
+    collision_layers: CollisionLayers,
+    parent: Option<Entity>,
+}
+
+impl<'w> EntityBuilder<'w> {
+    pub fn new(world: &'w mut World) -> Self {
+        Self {
+            world,
+            transform: Transform::default(),
+            sprite: None,
+            body: None,
+            collider: Collider::default(),
+            is_trigger: false,
+            collision_layers: CollisionLayers::default(),
+            parent: None,
+        }
+    }
+
+    pub fn at(mut self, position: Vec2) -> Self {
+        self.transform.position = position;
+        self
+    }
+
+    pub fn rotation(mut self, rotation: f32) -> Self {
+        self.transform.rotation = rotation;
+        self
+    }
+
+    pub fn scale(mut self, scale: Vec2) -> Self {
+        self.transform.scale = scale;
+        self
+    }
+
+    pub fn z(mut self, z: f32) -> Self {
+        self.transform.z = z;
+        self
+    }
+
+    // Replaces the whole local transform, including anything set by `at`.
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn sprite(mut self, sprite: Sprite) -> Self {
+        self.sprite = Some(sprite);
+        self
+    }
+
+    pub fn body(mut self, body: Body) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn collider(mut self, collider: Collider) -> Self {
+        self.collider = collider;
+        self
+    }
+
+    pub fn trigger(mut self, is_trigger: bool) -> Self {
+        self.is_trigger = is_trigger;
+        self
+    }
+
+    // Puts the entity on the layers in `mask`; see `World::iter_with_tag`.
+    pub fn tag(mut self, mask: u32) -> Self {
+        self.collision_layers.layers = mask;
+        self
+    }
+
+    pub fn collision_layers(mut self, layers: CollisionLayers) -> Self {
+        self.collision_layers = layers;
+        self
+    }
+
+    // The transform is then local to `parent`.
+    pub fn parent(mut self, parent: Entity) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    pub fn spawn(self) -> Entity {
+        let sprite = self.sprite.unwrap_or_else(|| Sprite {
+            visible: false,
+            ..Sprite::new(Vec2::ZERO, 0, Color::WHITE)
+        });
+        let world = self.world;
+        let entity = world.spawn_sprite_with_body(self.transform, sprite, self.body);
+        world.set_collider(entity, self.collider);
+        world.set_trigger(entity, self.is_trigger);
+        world.set_collision_layers(entity, self.collision_layers);
+        if let Some(parent) = self.parent {
+            if world.is_alive(parent) {
+                world.link_parent(entity, parent);
+            } else {
+                log::warn!(
+                    "Not parenting {:?} under despawned entity {:?}",
+                    entity,
+                    parent
+                );
+            }
+        }
+        entity
+    }
+}
+
+fn penetration(a: (Vec2, Vec2), b: (Vec2, Vec2)) -> Option<Vec2> {
+    let delta = b.0 - a.0;
+    let overlap = a.1 + b.1 - delta.abs();
+    if overlap.x <= 0.0 || overlap.y <= 0.0 {
+        return None;
+    }
+    if overlap.x < overlap.y {
+        let sign = if delta.x < 0.0 { -1.0 } else { 1.0 };
+        Some(Vec2::new(overlap.x * sign, 0.0))
+    } else {
+        let sign = if delta.y < 0.0 { -1.0 } else { 1.0 };
+        Some(Vec2::new(0.0, overlap.y * sign))
+    }
+}
+
+fn shape_penetration(a: Shape, b: Shape) -> Option<Vec2> {
+    match (a, b) {
+        (
+            Shape::Box {
+                center: ca,
+                half: ha,
+            },
+            Shape::Box {
+                center: cb,
+                half: hb,
+            },
+        ) => penetration((ca, ha), (cb, hb)),
+        (
+            Shape::Circle {
+                center: ca,
+                radius: ra,
+            },
+            Shape::Circle {
+                center: cb,
+                radius: rb,
+            },
+        ) => circle_penetration(ca, ra, cb, rb),
+        (Shape::Circle { center, radius }, Shape::Box { center: cb, half }) => {
+            circle_box_penetration(center, radius, cb, half)
+        }
+        (Shape::Box { center: cb, half }, Shape::Circle { center, radius }) => {
+            circle_box_penetration(center, radius, cb, half).map(|normal| -normal)
+        }
+    }
+}
+
+fn circle_penetration(a: Vec2, radius_a: f32, b: Vec2, radius_b: f32) -> Option<Vec2> {
+    let delta = b - a;
+    let distance = delta.length();
+    let overlap = radius_a + radius_b - distance;
+    if overlap <= 0.0 {
+        return None;
+    }
+    let direction = if distance > f32::EPSILON {
+        delta / distance
+    } else {
+        Vec2::X
+    };
+    Some(direction * overlap)
+}
+
+fn circle_box_penetration(center: Vec2, radius: f32, box_center: Vec2, half: Vec2) -> Option<Vec2> {
+    let closest = center.clamp(box_center - half, box_center + half);
+    let delta = closest - center;
+    let distance = delta.length();
+    if distance > f32::EPSILON {
+        if distance >= radius {
+            return None;
+        }
+        return Some(delta / distance * (radius - distance));
+    }
+    penetration((center, Vec2::splat(radius)), (box_center, half))
+}
+
+// Velocities after an impulse-based contact; an inverse mass of 0 is immovable.
+pub fn contact_velocities(
+    (velocity_a, inverse_mass_a): (Vec2, f32),
+    (velocity_b, inverse_mass_b): (Vec2, f32),
+    normal: Vec2,
+    restitution: f32,
+    friction: f32,
+) -> (Vec2, Vec2) {
+    let inverse_mass = inverse_mass_a + inverse_mass_b;
+    let relative = velocity_b - velocity_a;
+    let closing = relative.dot(normal);
+    if closing >= 0.0 || inverse_mass <= 0.0 {
+        return (velocity_a, velocity_b);
+    }
+    let impulse = -(1.0 + restitution) * closing / inverse_mass;
+    let tangent = (relative - normal * closing).normalize_or_zero();
+    let sliding = relative.dot(tangent);
+    let friction_impulse = (sliding / inverse_mass).min(friction.max(0.0) * impulse);
+    let total = normal * impulse - tangent * friction_impulse;
+    (
+        velocity_a - total * inverse_mass_a,
+        velocity_b + total * inverse_mass_b,
+    )
+}
+
+fn wrap_position(position: Vec2, bounds: Vec2) -> Vec2 {
+    let wrap = |value: f32, bound: f32| {
+        if bound > 0.0 {
+            (value + bound).rem_euclid(bound * 2.0) - bound
+        } else {
+            value
+        }
+    };
+    Vec2::new(wrap(position.x, bounds.x), wrap(position.y, bounds.y))
+}
+
+fn lerp_transforms(from: Transform, to: Transform, t: f32) -> Transform {
+    Transform {
+        position: from.position.lerp(to.position, t),
+        rotation: from.rotation + (to.rotation - from.rotation) * t,
+        scale: from.scale.lerp(to.scale, t),
+        z: from.z + (to.z - from.z) * t,
     }
 }
 
@@ -254,6 +2099,7 @@ fn combine_transforms(parent: Transform, local: Transform) -> Transform {
         position: parent.position + rotated,
         rotation: parent.rotation + local.rotation,
         scale: parent.scale * local.scale,
+        z: parent.z + local.z,
     }
 }
 
@@ -262,3 +2108,775 @@ fn rotate_vec2(value: Vec2, angle: f32) -> Vec2 {
     let s = angle.sin();
     Vec2::new(value.x * c - value.y * s, value.x * s + value.y * c)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite() -> Sprite {
+        Sprite::new(Vec2::ONE, 0, [1.0; 4])
+    }
+
+    #[test]
+    fn respawn_reuses_slot_and_stale_handle_fails() {
+        let mut world = World::new();
+        let first = world.spawn_sprite(Transform::new(Vec2::ZERO), sprite());
+        assert!(world.despawn(first));
+        assert!(!world.is_alive(first));
+
+        let second = world.spawn_sprite(Transform::new(Vec2::X), sprite());
+        assert_eq!(second.index, first.index);
+        assert_ne!(second.generation, first.generation);
+        assert!(world.is_alive(second));
+        assert!(!world.despawn(first));
+        assert!(world.get_transform_mut(first).is_none());
+        assert!(world.is_alive(second));
+    }
+
+    #[test]
+    fn body_drops_under_gravity_and_settles_on_lower_bound() {
+        let mut world = World::new();
+        world.set_gravity(Vec2::new(0.0, -20.0));
+        let body = Body {
+            bounce: 0.0,
+            ..Body::new(Vec2::ZERO)
+        };
+        let entity = world.spawn_sprite_with_body(
+            Transform::new(Vec2::new(0.0, 5.0)),
+            Sprite::new(Vec2::ZERO, 0, [1.0; 4]),
+            Some(body),
+        );
+        let bounds = Vec2::splat(10.0);
+        let dt = 1.0 / 60.0;
+        world.step_physics(dt, bounds);
+        let falling = world.get_transform_mut(entity).unwrap().position.y;
+        assert!(falling < 5.0);
+        for _ in 0..600 {
+            world.step_physics(dt, bounds);
+        }
+        let position = world.get_transform_mut(entity).unwrap().position;
+        assert_eq!(position, Vec2::new(0.0, -10.0));
+        let velocity = world.bodies[entity.index as usize]
+            .as_ref()
+            .unwrap()
+            .velocity;
+        assert_eq!(velocity.y, 0.0);
+    }
+
+    fn play(animation: &mut Animation, steps: usize) -> Vec<u32> {
+        (0..steps).filter_map(|_| animation.update(0.25)).collect()
+    }
+
+    #[test]
+    fn loop_animation_wraps_to_first_frame() {
+        let mut animation = Animation::new(vec![10, 11, 12], 4.0);
+        assert_eq!(play(&mut animation, 5), vec![11, 12, 10, 11, 12]);
+        assert!(!animation.finished());
+    }
+
+    #[test]
+    fn once_animation_holds_last_frame() {
+        let mut animation = Animation::new(vec![10, 11, 12], 4.0).with_mode(PlaybackMode::Once);
+        assert_eq!(play(&mut animation, 4), vec![11, 12, 12, 12]);
+        assert!(animation.finished());
+    }
+
+    #[test]
+    fn ping_pong_animation_reverses_without_repeating_ends() {
+        let mut animation = Animation::new(vec![10, 11, 12], 4.0).with_mode(PlaybackMode::PingPong);
+        assert_eq!(play(&mut animation, 6), vec![11, 12, 11, 10, 11, 12]);
+    }
+
+    #[test]
+    fn save_load_save_round_trip() {
+        let mut world = World::new();
+        let parent = world.spawn_sprite_with_body(
+            Transform {
+                rotation: 0.5,
+                ..Transform::new(Vec2::new(3.0, -2.0))
+            },
+            Sprite::new(Vec2::new(8.0, 4.0), 2, [0.5, 0.25, 1.0, 0.75]),
+            Some(Body::new(Vec2::new(1.0, 2.0))),
+        );
+        let child = world.spawn_sprite(
+            Transform {
+                scale: Vec2::splat(2.0),
+                ..Transform::new(Vec2::X)
+            },
+            sprite(),
+        );
+        world.set_collider(child, Collider::Circle { radius: 3.0 });
+        world.set_trigger(child, true);
+        assert!(world.set_parent(child, parent));
+
+        let saved = world.save_scene().to_json().unwrap();
+        let mut loaded = World::new();
+        let spawned = loaded.load_scene(&SceneFile::from_json(&saved).unwrap());
+        assert_eq!(spawned.len(), 2);
+        assert_eq!(loaded.parent(spawned[1]), Some(spawned[0]));
+        assert_eq!(loaded.save_scene().to_json().unwrap(), saved);
+    }
+
+    #[test]
+    fn spatial_hash_finds_clustered_neighbours_once() {
+        let entity = |index| Entity {
+            index,
+            generation: 0,
+        };
+        let mut hash = SpatialHash::new(1.0);
+        for i in 0..9 {
+            let center = Vec2::new(0.3 + (i % 3) as f32 * 0.2, 0.3 + (i / 3) as f32 * 0.2);
+            hash.insert(entity(i), center - 0.05, center + 0.05);
+        }
+        hash.insert(entity(9), Vec2::new(-2.0, -1.0), Vec2::new(1.9, 0.32));
+        hash.insert(entity(10), Vec2::splat(5.0), Vec2::splat(5.5));
+
+        let around_cluster = hash.query_aabb(Vec2::ZERO, Vec2::ONE);
+        assert_eq!(around_cluster, (0..10).map(entity).collect::<Vec<_>>());
+        assert_eq!(
+            hash.query_aabb(Vec2::new(-1.5, -0.5), Vec2::new(1.5, 0.3)),
+            vec![entity(0), entity(1), entity(2), entity(9)]
+        );
+        assert_eq!(
+            hash.query_aabb(Vec2::new(-2.0, -1.0), Vec2::new(-0.5, 0.0)),
+            vec![entity(9)]
+        );
+        assert_eq!(hash.query_point(Vec2::splat(5.2)), vec![entity(10)]);
+        assert!(hash.query_point(Vec2::splat(3.0)).is_empty());
+    }
+
+    #[test]
+    fn speed_is_clamped_to_max_and_snapped_below_min() {
+        let mut body = Body {
+            velocity: Vec2::new(30.0, 40.0),
+            max_speed: 10.0,
+            min_speed: 0.5,
+            ..Body::default()
+        };
+        body.clamp_speed();
+        assert!((body.velocity - Vec2::new(6.0, 8.0)).length() < 1e-5);
+        body.velocity = Vec2::new(0.3, 0.3);
+        body.clamp_speed();
+        assert_eq!(body.velocity, Vec2::ZERO);
+        body.max_speed = 0.0;
+        body.velocity = Vec2::splat(1000.0);
+        body.clamp_speed();
+        assert_eq!(body.velocity, Vec2::splat(1000.0));
+    }
+
+    #[test]
+    fn once_animation_reports_finished_exactly_once() {
+        let mut world = World::new();
+        let mut animated = sprite();
+        animated.animation = Some(Animation::new(vec![0, 1, 2], 4.0).with_mode(PlaybackMode::Once));
+        let entity = world.spawn_sprite(Transform::new(Vec2::ZERO), animated);
+        let mut finished = Vec::new();
+        for _ in 0..10 {
+            finished.extend(world.update_animations(0.25, false));
+        }
+        assert_eq!(finished, vec![(entity, AnimationEvent::Finished)]);
+    }
+
+    #[test]
+    fn circles_separate_along_the_centre_line() {
+        let push = circle_penetration(Vec2::ZERO, 3.0, Vec2::new(3.0, 4.0), 4.0).unwrap();
+        assert!((push - Vec2::new(1.2, 1.6)).length() < 1e-5);
+        assert_eq!(
+            circle_penetration(Vec2::ZERO, 1.0, Vec2::new(2.0, 0.0), 1.0),
+            None
+        );
+        assert_eq!(
+            circle_penetration(Vec2::ONE, 1.0, Vec2::ONE, 2.0),
+            Some(Vec2::new(3.0, 0.0))
+        );
+    }
+
+    fn drifting_body(velocity: Vec2) -> Body {
+        Body {
+            damping: 0.0,
+            use_gravity: false,
+            ..Body::new(velocity)
+        }
+    }
+
+    #[test]
+    fn passing_through_a_trigger_enters_and_exits_once() {
+        let mut world = World::new();
+        let zone = world.spawn_sprite(
+            Transform::new(Vec2::ZERO),
+            Sprite::new(Vec2::splat(4.0), 0, [1.0; 4]),
+        );
+        world.set_trigger(zone, true);
+        let mover = world.spawn_sprite_with_body(
+            Transform::new(Vec2::new(-10.0, 0.0)),
+            sprite(),
+            Some(drifting_body(Vec2::new(10.0, 0.0))),
+        );
+        let mut events = Vec::new();
+        for _ in 0..120 {
+            world.step_physics(1.0 / 60.0, Vec2::splat(100.0));
+            world.step_collisions();
+            events.extend_from_slice(world.trigger_events());
+        }
+        let event = |phase| TriggerEvent {
+            trigger: zone,
+            other: mover,
+            phase,
+        };
+        assert_eq!(
+            events,
+            vec![event(TriggerPhase::Enter), event(TriggerPhase::Exit)]
+        );
+        assert!(world.world_transform(mover).unwrap().position.x > 9.9);
+    }
+
+    #[test]
+    fn fade_is_halfway_at_half_duration() {
+        let mut fade = Fade::new(1.0, 0.0, 2.0);
+        assert_eq!(fade.update(1.0), 0.5);
+        assert!(!fade.finished());
+        assert_eq!(fade.update(5.0), 0.0);
+        assert!(fade.finished());
+
+        let mut world = World::new();
+        let mut faded = sprite();
+        faded.fade = Some(Fade::new(0.0, 1.0, 1.0));
+        let entity = world.spawn_sprite(Transform::new(Vec2::ZERO), faded);
+        world.update_animations(0.5, false);
+        let sprite = world.get_sprite_mut(entity).unwrap();
+        assert_eq!(sprite.color.a, 0.5);
+    }
+
+    #[test]
+    fn facing_follows_body_velocity() {
+        let mut world = World::new();
+        let mut walker = sprite();
+        walker.facing = Some(Facing::new(4, 3));
+        let entity = world.spawn_sprite_with_body(
+            Transform::new(Vec2::ZERO),
+            walker,
+            Some(drifting_body(Vec2::new(5.0, 1.0))),
+        );
+        world.update_animations(0.1, false);
+        assert_eq!(world.get_sprite_mut(entity).unwrap().tile_index, 4 + 3 * 3);
+        world.bodies[entity.index as usize]
+            .as_mut()
+            .unwrap()
+            .velocity = Vec2::new(0.0, 5.0);
+        world.update_animations(0.1, false);
+        assert_eq!(world.get_sprite_mut(entity).unwrap().tile_index, 4 + 3);
+        world.bodies[entity.index as usize]
+            .as_mut()
+            .unwrap()
+            .velocity = Vec2::new(-0.5, 0.0);
+        world.update_animations(0.1, false);
+        assert_eq!(world.get_sprite_mut(entity).unwrap().tile_index, 4 + 3);
+    }
+
+    #[test]
+    fn top_left_anchor_puts_the_corner_at_the_position() {
+        let mut panel = Sprite::new(Vec2::new(40.0, 20.0), 0, [1.0; 4]);
+        panel.anchor = Vec2::new(-0.5, 0.5);
+        let transform = Transform::new(Vec2::new(100.0, 50.0));
+        let (min, max) = panel.world_aabb(&transform);
+        assert!((Vec2::new(min.x, max.y) - transform.position).length() < 1e-4);
+        assert!((Vec2::new(max.x, min.y) - Vec2::new(140.0, 30.0)).length() < 1e-4);
+        for collider in [Collider::Aabb, Collider::Circle { radius: 5.0 }] {
+            let (center, _) = Shape::new(collider, &panel, &transform).bounds();
+            assert!((center - Vec2::new(120.0, 40.0)).length() < 1e-4);
+        }
+
+        let scaled = Transform {
+            scale: Vec2::splat(2.0),
+            ..transform
+        };
+        let (min, max) = panel.world_aabb(&scaled);
+        assert!((Vec2::new(min.x, max.y) - transform.position).length() < 1e-4);
+        assert!((Vec2::new(max.x, min.y) - Vec2::new(180.0, 10.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn parallax_offset_scales_with_distance_from_the_world() {
+        let camera = Vec2::new(200.0, -40.0);
+        let mut layer = sprite();
+        assert_eq!(layer.parallax_offset(camera), Vec2::ZERO);
+        layer.parallax = 0.5;
+        let position = Vec2::new(10.0, 10.0);
+        assert_eq!(
+            position + layer.parallax_offset(camera),
+            Vec2::new(110.0, -10.0)
+        );
+        layer.parallax = 0.0;
+        assert_eq!(position + layer.parallax_offset(camera) - camera, position);
+    }
+
+    #[test]
+    fn fast_body_does_not_tunnel_through_a_thin_wall() {
+        let mut world = World::new();
+        world.set_max_substeps(8);
+        let bullet = world.spawn_sprite_with_body(
+            Transform::new(Vec2::ZERO),
+            sprite(),
+            Some(drifting_body(Vec2::new(240.0, 0.0))),
+        );
+        world.spawn_sprite(Transform::new(Vec2::new(5.0, 0.0)), sprite());
+        assert_eq!(world.substep_count(1.0 / 60.0), 8);
+        let bounds = Vec2::splat(10.0);
+        for _ in 0..60 {
+            world.step_physics_substepped(1.0 / 60.0, bounds);
+            let position = world.world_transform(bullet).unwrap().position;
+            assert!(position.x < 4.5 + 1e-4);
+            assert!(position.abs().cmple(bounds).all());
+        }
+    }
+
+    #[test]
+    fn rotated_square_gets_a_wider_box() {
+        let square = sprite();
+        let transform = Transform {
+            rotation: std::f32::consts::FRAC_PI_4,
+            ..Transform::new(Vec2::new(3.0, -2.0))
+        };
+        let (min, max) = square.world_aabb(&transform);
+        let half = std::f32::consts::SQRT_2 * 0.5;
+        assert!((min - Vec2::new(3.0 - half, -2.0 - half)).length() < 1e-5);
+        assert!((max - Vec2::new(3.0 + half, -2.0 + half)).length() < 1e-5);
+    }
+
+    #[test]
+    fn kinematic_body_ignores_gravity() {
+        let mut world = World::new();
+        world.set_gravity(Vec2::new(0.0, -20.0));
+        let platform = world.spawn_sprite_with_body(
+            Transform::new(Vec2::ZERO),
+            sprite(),
+            Some(Body {
+                damping: 0.0,
+                ..Body::kinematic(Vec2::new(3.0, 0.0))
+            }),
+        );
+        let falling = world.spawn_sprite_with_body(
+            Transform::new(Vec2::new(0.0, 10.0)),
+            sprite(),
+            Some(Body {
+                damping: 0.0,
+                ..Body::default()
+            }),
+        );
+        for _ in 0..10 {
+            world.step_physics(0.1, Vec2::splat(100.0));
+        }
+        let velocity = world.bodies[platform.index as usize].unwrap().velocity;
+        assert_eq!(velocity, Vec2::new(3.0, 0.0));
+        let position = world.get_transform_mut(platform).unwrap().position;
+        assert!((position - Vec2::new(3.0, 0.0)).length() < 1e-4);
+        assert!(world.bodies[falling.index as usize].unwrap().velocity.y < -19.0);
+    }
+
+    fn cross_right_edge(behavior: BoundsBehavior) -> (Vec2, Vec2, Vec<Entity>) {
+        let mut world = World::new();
+        let body = Body {
+            bounds_behavior: behavior,
+            ..drifting_body(Vec2::new(60.0, 0.0))
+        };
+        let entity = world.spawn_sprite_with_body(
+            Transform::new(Vec2::new(9.5, 0.0)),
+            Sprite::new(Vec2::ZERO, 0, [1.0; 4]),
+            Some(body),
+        );
+        let destroyed = world.step_physics(1.0 / 60.0, Vec2::splat(10.0));
+        let position = world.world_transform(entity).unwrap().position;
+        let velocity = world.bodies[entity.index as usize].unwrap().velocity;
+        (position, velocity, destroyed)
+    }
+
+    #[test]
+    fn bounds_behaviors_handle_crossing_the_edge() {
+        let (position, velocity, destroyed) = cross_right_edge(BoundsBehavior::Bounce);
+        assert_eq!(position.x, 10.0);
+        assert_eq!(velocity.x, -60.0 * 0.75);
+        assert!(destroyed.is_empty());
+
+        let (position, velocity, destroyed) = cross_right_edge(BoundsBehavior::Wrap);
+        assert!((position.x + 9.5).abs() < 1e-4);
+        assert_eq!(velocity.x, 60.0);
+        assert!(destroyed.is_empty());
+
+        let (position, velocity, destroyed) = cross_right_edge(BoundsBehavior::Clamp);
+        assert_eq!(position.x, 10.0);
+        assert_eq!(velocity.x, 0.0);
+        assert!(destroyed.is_empty());
+
+        let (position, velocity, destroyed) = cross_right_edge(BoundsBehavior::Destroy);
+        assert!((position.x - 10.5).abs() < 1e-4);
+        assert_eq!(velocity.x, 60.0);
+        assert_eq!(destroyed.len(), 1);
+    }
+
+    #[test]
+    fn colliders_follow_a_scaled_parent() {
+        let mut world = World::new();
+        let parent = world.spawn_sprite(
+            Transform {
+                scale: Vec2::new(2.0, 3.0),
+                ..Transform::new(Vec2::new(10.0, 0.0))
+            },
+            sprite(),
+        );
+        let boxed = world.spawn_sprite(Transform::new(Vec2::new(1.0, 0.0)), sprite());
+        world.set_parent(boxed, parent);
+        let (min, max) = world.collision_bounds(boxed).unwrap();
+        assert!((min - Vec2::new(11.0, -1.5)).length() < 1e-5);
+        assert!((max - Vec2::new(13.0, 1.5)).length() < 1e-5);
+
+        let round = world.spawn_sprite(Transform::new(Vec2::ZERO), sprite());
+        world.set_collider(round, Collider::Circle { radius: 0.5 });
+        world.set_parent(round, parent);
+        let (min, max) = world.collision_bounds(round).unwrap();
+        assert!((min - Vec2::new(8.5, -1.5)).length() < 1e-5);
+        assert!((max - Vec2::new(11.5, 1.5)).length() < 1e-5);
+    }
+
+    #[test]
+    fn pushed_parent_carries_its_cached_children() {
+        let mut world = World::new();
+        let parent = world.spawn_sprite_with_body(
+            Transform::new(Vec2::ZERO),
+            sprite(),
+            Some(drifting_body(Vec2::ZERO)),
+        );
+        let child = world.spawn_sprite(Transform::new(Vec2::new(0.0, 3.0)), sprite());
+        let grandchild = world.spawn_sprite(Transform::new(Vec2::new(0.0, 3.0)), sprite());
+        world.set_parent(child, parent);
+        world.set_parent(grandchild, child);
+        world.spawn_sprite(Transform::new(Vec2::new(0.8, 0.0)), sprite());
+        assert_eq!(world.step_collisions().len(), 1);
+        for entity in [parent, child, grandchild] {
+            let cached = world.world_cache[entity.index as usize].unwrap().position;
+            assert_eq!(cached, world.world_transform(entity).unwrap().position);
+        }
+        assert!(world.world_transform(grandchild).unwrap().position.x < 0.0);
+    }
+
+    #[test]
+    fn hidden_sprites_are_not_visited() {
+        let mut world = World::new();
+        let shown = world.spawn_sprite(Transform::new(Vec2::ZERO), sprite());
+        let hidden = world.spawn_sprite(Transform::new(Vec2::ZERO), sprite());
+        world.set_visible(hidden, false);
+        let mut visited = Vec::new();
+        world.for_each_sprite_world(1.0, |entity, _, _| visited.push(entity));
+        assert_eq!(visited, vec![shown]);
+        visited.clear();
+        world.for_each_sprite_world_ordered(1.0, |entity, _, _| visited.push(entity));
+        assert_eq!(visited, vec![shown]);
+
+        world.set_visible(hidden, true);
+        visited.clear();
+        world.for_each_sprite_world_ordered(1.0, |entity, _, _| visited.push(entity));
+        assert_eq!(visited, vec![shown, hidden]);
+    }
+
+    #[test]
+    fn child_rotation_composes_with_its_parent() {
+        use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+        let mut world = World::new();
+        let parent = world.spawn_sprite(
+            Transform {
+                rotation: FRAC_PI_2,
+                ..Transform::new(Vec2::new(5.0, 0.0))
+            },
+            sprite(),
+        );
+        let child = world.spawn_sprite(
+            Transform {
+                rotation: FRAC_PI_4,
+                ..Transform::new(Vec2::new(2.0, 0.0))
+            },
+            sprite(),
+        );
+        world.set_parent(child, parent);
+        let transform = world.world_transform(child).unwrap();
+        assert!((transform.position - Vec2::new(5.0, 2.0)).length() < 1e-5);
+        assert!((transform.rotation - (FRAC_PI_2 + FRAC_PI_4)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ordered_iteration_survives_slot_reuse() {
+        let mut world = World::new();
+        let first = world.spawn_sprite(Transform::new(Vec2::ZERO), sprite());
+        let second = world.spawn_sprite(Transform::new(Vec2::ZERO), sprite());
+        let third = world.spawn_sprite(Transform::new(Vec2::ZERO), sprite());
+        world.despawn(first);
+        let fourth = world.spawn_sprite(Transform::new(Vec2::ZERO), sprite());
+        assert_eq!(fourth.index, first.index);
+
+        let mut by_slot = Vec::new();
+        world.for_each_sprite_world(1.0, |entity, _, _| by_slot.push(entity));
+        assert_eq!(by_slot, vec![fourth, second, third]);
+        let mut ordered = Vec::new();
+        world.for_each_sprite_world_ordered(1.0, |entity, _, _| ordered.push(entity));
+        assert_eq!(ordered, vec![second, third, fourth]);
+
+        world.despawn(third);
+        let fifth = world.spawn_sprite(Transform::new(Vec2::ZERO), sprite());
+        ordered.clear();
+        world.for_each_sprite_world_ordered(1.0, |entity, _, _| ordered.push(entity));
+        assert_eq!(ordered, vec![second, fourth, fifth]);
+    }
+
+    #[test]
+    fn only_unpausable_animations_run_while_paused() {
+        let mut world = World::new();
+        let mut gameplay = sprite();
+        gameplay.animation = Some(Animation::new(vec![0, 1, 2, 3], 10.0));
+        let mut menu = sprite();
+        menu.animation = Some(Animation::new(vec![0, 1, 2, 3], 10.0).with_pausable(false));
+        let gameplay = world.spawn_sprite(Transform::new(Vec2::ZERO), gameplay);
+        let menu = world.spawn_sprite(Transform::new(Vec2::ZERO), menu);
+        world.update_animations(0.25, true);
+        assert_eq!(world.get_sprite_mut(gameplay).unwrap().tile_index, 0);
+        assert_eq!(world.get_sprite_mut(menu).unwrap().tile_index, 2);
+
+        world.update_animations(0.1, false);
+        assert_eq!(world.get_sprite_mut(gameplay).unwrap().tile_index, 1);
+        assert_eq!(world.get_sprite_mut(menu).unwrap().tile_index, 3);
+    }
+
+    #[test]
+    fn three_layers_collide_only_where_masks_allow() {
+        const PLAYER: u32 = 1;
+        const ENEMY: u32 = 2;
+        const PICKUP: u32 = 4;
+        let player_layers = CollisionLayers::new(PLAYER, ENEMY | PICKUP);
+        let enemy_layers = CollisionLayers::new(ENEMY, PLAYER);
+        let pickup_layers = CollisionLayers::new(PICKUP, PLAYER);
+        assert!(player_layers.interacts_with(enemy_layers));
+        assert!(player_layers.interacts_with(pickup_layers));
+        assert!(!enemy_layers.interacts_with(enemy_layers));
+        assert!(!enemy_layers.interacts_with(pickup_layers));
+
+        let mut world = World::new();
+        let mut spawn = |x: f32, layers: CollisionLayers| {
+            let entity = world.spawn_sprite_with_body(
+                Transform::new(Vec2::new(x, 0.0)),
+                sprite(),
+                Some(drifting_body(Vec2::ZERO)),
+            );
+            world.set_collision_layers(entity, layers);
+            entity
+        };
+        let player = spawn(0.0, player_layers);
+        let enemy = spawn(0.5, enemy_layers);
+        spawn(1.3, enemy_layers);
+        let pickup = spawn(-0.5, pickup_layers);
+        let mut pairs: Vec<(Entity, Entity)> = world
+            .step_collisions()
+            .into_iter()
+            .map(|event| (event.a, event.b))
+            .collect();
+        pairs.sort_by_key(|&(a, b)| (a.index, b.index));
+        assert_eq!(pairs, vec![(player, enemy), (player, pickup)]);
+    }
+
+    #[test]
+    fn builder_stores_every_component() {
+        let mut world = World::new();
+        let parent = world.build().spawn();
+        let layers = CollisionLayers::new(2, 1);
+        let entity = world
+            .build()
+            .at(Vec2::new(1.0, 2.0))
+            .rotation(0.5)
+            .scale(Vec2::splat(3.0))
+            .z(4.0)
+            .sprite(Sprite::new(Vec2::new(8.0, 6.0), 7, Color::WHITE))
+            .body(drifting_body(Vec2::new(5.0, 0.0)))
+            .collider(Collider::Circle { radius: 2.0 })
+            .trigger(true)
+            .collision_layers(layers)
+            .parent(parent)
+            .spawn();
+        let index = entity.index as usize;
+        assert_eq!(
+            world.transforms[index],
+            Some(Transform {
+                position: Vec2::new(1.0, 2.0),
+                rotation: 0.5,
+                scale: Vec2::splat(3.0),
+                z: 4.0,
+            })
+        );
+        let sprite = world.sprites[index].as_ref().unwrap();
+        assert_eq!((sprite.size, sprite.tile_index), (Vec2::new(8.0, 6.0), 7));
+        assert_eq!(world.bodies[index].unwrap().velocity, Vec2::new(5.0, 0.0));
+        assert_eq!(world.colliders[index], Collider::Circle { radius: 2.0 });
+        assert!(world.triggers[index]);
+        assert_eq!(world.collision_layers[index], layers);
+        assert_eq!(world.parent(entity), Some(parent));
+    }
+
+    #[test]
+    fn tint_keeps_the_current_alpha() {
+        let mut tinted = sprite();
+        tinted.set_alpha(0.25);
+        tinted.set_tint(1.0, 0.5, 0.0);
+        assert_eq!(tinted.color, Color::rgba(1.0, 0.5, 0.0, 0.25));
+        tinted.set_tint_color(Color::rgba(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(tinted.color, Color::rgba(0.0, 0.0, 1.0, 0.25));
+    }
+
+    #[test]
+    fn uv_scroll_accumulates_and_wraps() {
+        let mut world = World::new();
+        let mut water = sprite();
+        water.uv_scroll = Vec2::new(0.25, -0.5);
+        let entity = world.build().sprite(water).spawn();
+        world.update_animations(0.5, false);
+        world.update_animations(0.5, false);
+        let offset = world.get_sprite_mut(entity).unwrap().uv_offset;
+        assert!((offset - Vec2::new(0.25, 0.5)).length() < 1e-5);
+        world.update_animations(0.5, true);
+        assert_eq!(world.get_sprite_mut(entity).unwrap().uv_offset, offset);
+    }
+
+    #[test]
+    fn despawn_outside_keeps_bodies_within_the_margin() {
+        let mut world = World::new();
+        let playfield = Vec2::new(100.0, 50.0);
+        let margin = Vec2::splat(10.0);
+        let mut spawn_at = |position: Vec2| {
+            world
+                .build()
+                .at(position)
+                .sprite(sprite())
+                .body(drifting_body(Vec2::ZERO))
+                .spawn()
+        };
+        let inside = spawn_at(Vec2::new(90.0, 0.0));
+        let in_margin = spawn_at(Vec2::new(-105.0, 55.0));
+        let past_margin = spawn_at(Vec2::new(0.0, -61.0));
+        let far_away = spawn_at(Vec2::new(500.0, 0.0));
+        let scenery = world.build().at(Vec2::new(500.0, 0.0)).spawn();
+
+        let mut despawned = world.despawn_outside(playfield + margin);
+        despawned.sort_by_key(|entity| entity.index);
+        assert_eq!(despawned, vec![past_margin, far_away]);
+        assert!(world.is_alive(inside));
+        assert!(world.is_alive(in_margin));
+        assert!(world.is_alive(scenery));
+    }
+
+    #[test]
+    fn child_z_adds_to_the_parent_z() {
+        let mut world = World::new();
+        let parent = world.build().z(2.0).spawn();
+        let child = world.build().z(0.5).sprite(sprite()).parent(parent).spawn();
+        let grandchild = world.build().z(-1.0).sprite(sprite()).parent(child).spawn();
+        assert_eq!(world.world_transform(child).unwrap().z, 2.5);
+        assert_eq!(world.world_transform(grandchild).unwrap().z, 1.5);
+
+        world.get_transform_mut(parent).unwrap().z = -3.0;
+        let mut depths = Vec::new();
+        world.for_each_sprite_world(1.0, |_, transform, _| depths.push(transform.z));
+        assert_eq!(depths, vec![-2.5, -3.5]);
+    }
+
+    #[test]
+    fn reparenting_moves_a_three_level_chain() {
+        let mut world = World::new();
+        let root = world.build().at(Vec2::new(10.0, 0.0)).spawn();
+        let other_root = world.build().at(Vec2::new(0.0, 50.0)).spawn();
+        let middle = world.build().at(Vec2::new(1.0, 0.0)).parent(root).spawn();
+        let leaf = world.build().at(Vec2::new(0.0, 1.0)).parent(middle).spawn();
+        world.for_each_sprite_world(1.0, |_, _, _| {});
+        assert_eq!(
+            world.world_cache[leaf.index as usize].unwrap().position,
+            Vec2::new(11.0, 1.0)
+        );
+
+        assert!(world.set_parent(middle, other_root));
+        assert!(world.world_cache[middle.index as usize].is_none());
+        assert!(world.world_cache[leaf.index as usize].is_none());
+        assert_eq!(
+            world.world_transform(leaf).unwrap().position,
+            Vec2::new(1.0, 51.0)
+        );
+        world.for_each_sprite_world(1.0, |_, _, _| {});
+        assert_eq!(
+            world.world_cache[leaf.index as usize].unwrap().position,
+            Vec2::new(1.0, 51.0)
+        );
+
+        assert!(!world.set_parent(other_root, leaf));
+        assert_eq!(world.parent(other_root), None);
+    }
+
+    #[test]
+    fn head_on_contact_swaps_equal_masses_and_weights_unequal_ones() {
+        let (a, b) = contact_velocities(
+            (Vec2::new(2.0, 0.0), 1.0),
+            (Vec2::new(-2.0, 0.0), 1.0),
+            Vec2::X,
+            1.0,
+            0.0,
+        );
+        assert_eq!((a, b), (Vec2::new(-2.0, 0.0), Vec2::new(2.0, 0.0)));
+
+        let (a, b) = contact_velocities(
+            (Vec2::new(4.0, 0.0), 1.0),
+            (Vec2::ZERO, 1.0 / 3.0),
+            Vec2::X,
+            0.0,
+            0.0,
+        );
+        assert!((a - Vec2::new(1.0, 0.0)).length() < 1e-5);
+        assert!((b - Vec2::new(1.0, 0.0)).length() < 1e-5);
+
+        let apart = (Vec2::new(-1.0, 0.0), Vec2::new(1.0, 0.0));
+        assert_eq!(
+            contact_velocities((apart.0, 1.0), (apart.1, 1.0), Vec2::X, 1.0, 0.0),
+            apart
+        );
+    }
+
+    #[test]
+    fn glancing_contact_keeps_tangent_speed_up_to_the_friction_limit() {
+        let incoming = Vec2::new(3.0, -3.0);
+        let (frictionless, _) =
+            contact_velocities((incoming, 1.0), (Vec2::ZERO, 0.0), -Vec2::Y, 0.5, 0.0);
+        assert!((frictionless - Vec2::new(3.0, 1.5)).length() < 1e-5);
+
+        let (rough, _) = contact_velocities((incoming, 1.0), (Vec2::ZERO, 0.0), -Vec2::Y, 0.5, 0.5);
+        assert!((rough - Vec2::new(0.75, 1.5)).length() < 1e-5);
+
+        let (sticky, _) =
+            contact_velocities((incoming, 1.0), (Vec2::ZERO, 0.0), -Vec2::Y, 0.5, 10.0);
+        assert!((sticky - Vec2::new(0.0, 1.5)).length() < 1e-5);
+    }
+
+    #[test]
+    fn destroyed_bodies_sit_out_the_remaining_substeps() {
+        let mut world = World::new();
+        world.set_max_substeps(8);
+        let body = Body {
+            bounds_behavior: BoundsBehavior::Destroy,
+            ..drifting_body(Vec2::new(600.0, 0.0))
+        };
+        let bullet = world
+            .build()
+            .at(Vec2::new(9.5, 0.0))
+            .sprite(sprite())
+            .body(body)
+            .spawn();
+        world
+            .build()
+            .at(Vec2::new(17.0, 0.0))
+            .sprite(sprite())
+            .spawn();
+        let (events, destroyed) = world.step_physics_substepped(1.0 / 60.0, Vec2::splat(10.0));
+        assert_eq!(destroyed, vec![bullet]);
+        assert!(events.is_empty());
+        assert!(world.world_transform(bullet).unwrap().position.x < 12.0);
+    }
+}
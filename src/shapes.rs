@@ -0,0 +1,104 @@
+use glam::Vec2;
+
+use crate::color::Color;
+
+// Filled shapes drawn from a signed distance field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShapeKind {
+    // The largest circle that fits the instance size.
+    Circle,
+    RoundedRect,
+}
+
+impl ShapeKind {
+    fn to_raw(self) -> u32 {
+        match self {
+            ShapeKind::Circle => 0,
+            ShapeKind::RoundedRect => 1,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShapeInstance {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    // Linear; `new` takes an sRGB color like `Sprite::color` and converts it.
+    pub color: [f32; 4],
+    pub corner_radius: f32,
+    // Width of the anti-aliased edge in screen pixels; 1.0 is a crisp edge.
+    pub softness: f32,
+    kind: u32,
+}
+
+impl ShapeInstance {
+    const ATTRIBS: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+        2 => Float32x2,
+        3 => Float32x2,
+        4 => Float32x4,
+        5 => Float32,
+        6 => Float32,
+        7 => Uint32
+    ];
+
+    pub(crate) fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ShapeInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    pub fn new(kind: ShapeKind, center: Vec2, size: Vec2, color: [f32; 4]) -> Self {
+        Self {
+            position: [center.x, center.y],
+            size: [size.x, size.y],
+            color: Color::from(color).to_linear(),
+            corner_radius: 0.0,
+            softness: 1.0,
+            kind: kind.to_raw(),
+        }
+    }
+}
+
+// World-space shapes drawn over the sprites, under text and debug lines.
+#[derive(Default)]
+pub struct Shapes {
+    instances: Vec<ShapeInstance>,
+}
+
+impl Shapes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn circle(&mut self, center: Vec2, radius: f32, color: [f32; 4]) {
+        self.push(ShapeInstance::new(
+            ShapeKind::Circle,
+            center,
+            Vec2::splat(radius * 2.0),
+            color,
+        ));
+    }
+
+    // `corner_radius` is clamped to half the shorter side by the shader.
+    pub fn rounded_rect(&mut self, min: Vec2, max: Vec2, corner_radius: f32, color: [f32; 4]) {
+        self.push(ShapeInstance {
+            corner_radius,
+            ..ShapeInstance::new(ShapeKind::RoundedRect, (min + max) * 0.5, max - min, color)
+        });
+    }
+
+    pub fn push(&mut self, instance: ShapeInstance) {
+        self.instances.push(instance);
+    }
+
+    pub fn instances(&self) -> &[ShapeInstance] {
+        &self.instances
+    }
+
+    pub fn clear(&mut self) {
+        self.instances.clear();
+    }
+}
@@ -0,0 +1,194 @@
+use glam::Vec2;
+
+use crate::assets::{Atlas, FrameRect, Texture, TextureOptions};
+use crate::renderer::InstanceRaw;
+use crate::scene::{Sprite, Transform};
+
+const BUILTIN_FIRST_CHAR: char = ' ';
+const BUILTIN_COLUMNS: u32 = 16;
+const BUILTIN_CELL: (u32, u32) = (4, 6);
+
+const BUILTIN_GLYPHS: [[u8; 5]; 64] = [
+    [0, 0, 0, 0, 0], // space
+    [2, 2, 2, 0, 2], // !
+    [5, 5, 0, 0, 0], // "
+    [5, 7, 5, 7, 5], // #
+    [3, 6, 7, 3, 6], // $
+    [5, 1, 2, 4, 5], // %
+    [2, 5, 2, 5, 3], // &
+    [2, 2, 0, 0, 0], // '
+    [1, 2, 2, 2, 1], // (
+    [4, 2, 2, 2, 4], // )
+    [0, 5, 2, 5, 0], // *
+    [0, 2, 7, 2, 0], // +
+    [0, 0, 0, 2, 4], // ,
+    [0, 0, 7, 0, 0], // -
+    [0, 0, 0, 0, 2], // .
+    [1, 1, 2, 4, 4], // /
+    [7, 5, 5, 5, 7], // 0
+    [2, 6, 2, 2, 7], // 1
+    [7, 1, 7, 4, 7], // 2
+    [7, 1, 3, 1, 7], // 3
+    [5, 5, 7, 1, 1], // 4
+    [7, 4, 7, 1, 7], // 5
+    [7, 4, 7, 5, 7], // 6
+    [7, 1, 1, 1, 1], // 7
+    [7, 5, 7, 5, 7], // 8
+    [7, 5, 7, 1, 7], // 9
+    [0, 2, 0, 2, 0], // :
+    [0, 2, 0, 2, 4], // ;
+    [1, 2, 4, 2, 1], // <
+    [0, 7, 0, 7, 0], // =
+    [4, 2, 1, 2, 4], // >
+    [7, 1, 3, 0, 2], // ?
+    [7, 5, 7, 4, 7], // @
+    [2, 5, 7, 5, 5], // A
+    [6, 5, 6, 5, 6], // B
+    [3, 4, 4, 4, 3], // C
+    [6, 5, 5, 5, 6], // D
+    [7, 4, 6, 4, 7], // E
+    [7, 4, 6, 4, 4], // F
+    [3, 4, 5, 5, 3], // G
+    [5, 5, 7, 5, 5], // H
+    [7, 2, 2, 2, 7], // I
+    [1, 1, 1, 5, 2], // J
+    [5, 5, 6, 5, 5], // K
+    [4, 4, 4, 4, 7], // L
+    [5, 7, 7, 5, 5], // M
+    [6, 5, 5, 5, 5], // N
+    [2, 5, 5, 5, 2], // O
+    [6, 5, 6, 4, 4], // P
+    [2, 5, 5, 6, 3], // Q
+    [6, 5, 6, 5, 5], // R
+    [3, 4, 2, 1, 6], // S
+    [7, 2, 2, 2, 2], // T
+    [5, 5, 5, 5, 7], // U
+    [5, 5, 5, 5, 2], // V
+    [5, 5, 7, 7, 5], // W
+    [5, 5, 2, 5, 5], // X
+    [5, 5, 2, 2, 2], // Y
+    [7, 1, 2, 4, 7], // Z
+    [3, 2, 2, 2, 3], // [
+    [4, 4, 2, 1, 1], // \
+    [6, 2, 2, 2, 6], // ]
+    [2, 5, 0, 0, 0], // ^
+    [0, 0, 0, 0, 7], // _
+];
+
+// A monospace font whose glyphs are consecutive atlas tiles.
+pub struct BitmapFont {
+    texture: Texture,
+    atlas: Atlas,
+    glyph_size: Vec2,
+    first_char: char,
+}
+
+impl BitmapFont {
+    pub fn new(texture: Texture, atlas: Atlas, glyph_size: Vec2, first_char: char) -> Self {
+        Self {
+            texture,
+            atlas,
+            glyph_size,
+            first_char,
+        }
+    }
+
+    pub fn builtin(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let rows = (BUILTIN_GLYPHS.len() as u32).div_ceil(BUILTIN_COLUMNS);
+        let (cell_w, cell_h) = BUILTIN_CELL;
+        let width = BUILTIN_COLUMNS * cell_w;
+        let height = rows * cell_h;
+        let mut texels = vec![0u8; (width * height * 4) as usize];
+        let mut frames = Vec::with_capacity(BUILTIN_GLYPHS.len());
+        for (index, glyph) in BUILTIN_GLYPHS.iter().enumerate() {
+            let x0 = (index as u32 % BUILTIN_COLUMNS) * cell_w;
+            let y0 = (index as u32 / BUILTIN_COLUMNS) * cell_h;
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (4 >> column) == 0 {
+                        continue;
+                    }
+                    let x = x0 + column;
+                    let y = y0 + row as u32;
+                    let offset = ((y * width + x) * 4) as usize;
+                    texels[offset..offset + 4].copy_from_slice(&[255, 255, 255, 255]);
+                }
+            }
+            frames.push(FrameRect {
+                x: x0,
+                y: y0,
+                w: cell_w,
+                h: cell_h,
+            });
+        }
+
+        let texture = Texture::from_rgba8(
+            device,
+            queue,
+            width,
+            height,
+            &texels,
+            "Builtin Font",
+            TextureOptions::default(),
+        )
+        .expect("builtin font texture is valid");
+        let mut atlas =
+            Atlas::with_tile_size(BUILTIN_COLUMNS, rows, cell_w, cell_h).with_frames(frames);
+        atlas.set_texture_size(width, height);
+        Self::new(
+            texture,
+            atlas,
+            Vec2::new(cell_w as f32, cell_h as f32),
+            BUILTIN_FIRST_CHAR,
+        )
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn glyph_size(&self) -> Vec2 {
+        self.glyph_size
+    }
+
+    fn tile_for(&self, c: char) -> Option<u32> {
+        let lookup = |c: char| {
+            let index = (c as u32).checked_sub(self.first_char as u32)?;
+            (index < self.atlas.tile_count()).then_some(index)
+        };
+        lookup(c)
+            .or_else(|| lookup(c.to_ascii_uppercase()))
+            .or_else(|| lookup('?'))
+    }
+
+    // Appends one instance per visible glyph; `pos` is the top-left corner.
+    pub fn layout(
+        &self,
+        text: &str,
+        pos: Vec2,
+        scale: f32,
+        color: [f32; 4],
+        out: &mut Vec<InstanceRaw>,
+    ) {
+        let size = self.glyph_size * scale;
+        let mut cursor = pos;
+        for c in text.chars() {
+            if c == '\n' {
+                cursor = Vec2::new(pos.x, cursor.y - size.y);
+                continue;
+            }
+            if c != ' '
+                && let Some(tile_index) = self.tile_for(c)
+            {
+                let center = cursor + Vec2::new(size.x, -size.y) * 0.5;
+                let sprite = Sprite::new(size, tile_index, color);
+                out.push(InstanceRaw::from_components(
+                    &Transform::new(center),
+                    &sprite,
+                    &self.atlas,
+                ));
+            }
+            cursor.x += size.x;
+        }
+    }
+}
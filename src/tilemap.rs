@@ -0,0 +1,188 @@
+use glam::Vec2;
+
+use crate::assets::Atlas;
+use crate::color::Color;
+use crate::renderer::InstanceRaw;
+use crate::scene::{Sprite, Transform};
+
+// Cells hold `tile index + 1` so that 0 means empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileMap {
+    width: u32,
+    height: u32,
+    tiles: Vec<u32>,
+    // World size of one cell.
+    pub tile_size: Vec2,
+    pub origin: Vec2,
+    pub texture_id: u32,
+    pub layer: i32,
+    pub order: i64,
+    pub color: Color,
+    pub visible: bool,
+}
+
+impl TileMap {
+    // An empty map.
+    pub fn new(width: u32, height: u32, tile_size: Vec2) -> Self {
+        Self::from_tiles(width, height, tile_size, Vec::new())
+    }
+
+    // `tiles` is row-major from the top-left.
+    pub fn from_tiles(width: u32, height: u32, tile_size: Vec2, mut tiles: Vec<u32>) -> Self {
+        let cells = width as usize * height as usize;
+        if !tiles.is_empty() && tiles.len() != cells {
+            log::warn!(
+                "Tilemap has {} cells but {} tiles were given, resizing",
+                cells,
+                tiles.len()
+            );
+        }
+        tiles.resize(cells, 0);
+        Self {
+            width,
+            height,
+            tiles,
+            tile_size,
+            origin: Vec2::ZERO,
+            texture_id: 0,
+            layer: 0,
+            order: 0,
+            color: Color::WHITE,
+            visible: true,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn tiles(&self) -> &[u32] {
+        &self.tiles
+    }
+
+    // The raw cell value; None outside the map.
+    pub fn get(&self, column: u32, row: u32) -> Option<u32> {
+        self.cell_index(column, row).map(|index| self.tiles[index])
+    }
+
+    // Returns false outside the map.
+    pub fn set(&mut self, column: u32, row: u32, value: u32) -> bool {
+        match self.cell_index(column, row) {
+            Some(index) => {
+                self.tiles[index] = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cell_center(&self, column: u32, row: u32) -> Vec2 {
+        self.origin
+            + Vec2::new(
+                (column as f32 + 0.5) * self.tile_size.x,
+                -(row as f32 + 0.5) * self.tile_size.y,
+            )
+    }
+
+    // The (column, row) under a world position, if it is on the map.
+    pub fn cell_at(&self, position: Vec2) -> Option<(u32, u32)> {
+        let local = (position - self.origin) / self.tile_size;
+        let (column, row) = (local.x.floor(), (-local.y).floor());
+        let on_map =
+            column >= 0.0 && row >= 0.0 && column < self.width as f32 && row < self.height as f32;
+        on_map.then_some((column as u32, row as u32))
+    }
+
+    // Visits non-empty cells overlapping the world rectangle `min`..`max`.
+    pub fn for_each_tile_in(&self, min: Vec2, max: Vec2, mut f: impl FnMut(Vec2, u32)) {
+        if self.width == 0 || self.height == 0 || self.tile_size.cmple(Vec2::ZERO).any() {
+            return;
+        }
+        let first = ((Vec2::new(min.x, -max.y) - Vec2::new(self.origin.x, -self.origin.y))
+            / self.tile_size)
+            .floor();
+        let last = ((Vec2::new(max.x, -min.y) - Vec2::new(self.origin.x, -self.origin.y))
+            / self.tile_size)
+            .floor();
+        let columns = clamp_cells(first.x, last.x, self.width);
+        let rows = clamp_cells(first.y, last.y, self.height);
+        for row in rows {
+            for column in columns.clone() {
+                let value = self.tiles[row as usize * self.width as usize + column as usize];
+                if value != 0 {
+                    f(self.cell_center(column, row), value - 1);
+                }
+            }
+        }
+    }
+
+    // Every non-empty cell as a sprite instance, in row-major order.
+    pub fn instances(&self, atlas: &Atlas) -> Vec<InstanceRaw> {
+        let mut instances = Vec::new();
+        let (min, max) = self.bounds();
+        self.for_each_tile_in(min, max, |center, tile| {
+            instances.push(self.instance(center, tile, atlas));
+        });
+        instances
+    }
+
+    pub fn instance(&self, center: Vec2, tile_index: u32, atlas: &Atlas) -> InstanceRaw {
+        let transform = Transform {
+            position: center,
+            ..Transform::default()
+        };
+        let sprite = Sprite {
+            texture_id: self.texture_id,
+            ..Sprite::new(self.tile_size, tile_index, self.color)
+        };
+        InstanceRaw::from_components(&transform, &sprite, atlas)
+    }
+
+    // (min, max) world corners of the whole map.
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        let size = Vec2::new(self.width as f32, self.height as f32) * self.tile_size;
+        (
+            Vec2::new(self.origin.x, self.origin.y - size.y),
+            Vec2::new(self.origin.x + size.x, self.origin.y),
+        )
+    }
+
+    fn cell_index(&self, column: u32, row: u32) -> Option<usize> {
+        (column < self.width && row < self.height)
+            .then(|| row as usize * self.width as usize + column as usize)
+    }
+}
+
+fn clamp_cells(first: f32, last: f32, count: u32) -> std::ops::Range<u32> {
+    let start = first.max(0.0) as u32;
+    let end = ((last + 1.0).max(0.0) as u32).min(count);
+    start.min(end)..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instances_skip_empty_cells_in_row_major_order() {
+        let map = TileMap::from_tiles(3, 2, Vec2::splat(10.0), vec![1, 0, 3, 0, 4, 0]);
+        let atlas = Atlas::new(2, 2, 8);
+        let instances = map.instances(&atlas);
+        assert_eq!(instances.len(), 3);
+        let expected = [
+            (Vec2::new(5.0, -5.0), 0),
+            (Vec2::new(25.0, -5.0), 2),
+            (Vec2::new(15.0, -15.0), 3),
+        ];
+        for (instance, (center, tile)) in instances.iter().zip(expected) {
+            let expected = map.instance(center, tile, &atlas);
+            assert_eq!(bytemuck::bytes_of(instance), bytemuck::bytes_of(&expected));
+        }
+        assert_eq!(map.get(1, 1), Some(4));
+        assert_eq!(map.get(3, 0), None);
+    }
+}
@@ -1,57 +1,271 @@
+use std::collections::VecDeque;
 use std::time::Instant;
 use winit::window::Window;
 
 const MAX_DT: f32 = 0.25;
+const MAX_CATCH_UP: f32 = 5.0 / 60.0;
+const MIN_FIXED_DT: f32 = 0.001;
+const MAX_FIXED_DT: f32 = 1.0;
+const DEFAULT_FIXED_DT: f32 = 1.0 / 60.0;
+const STATS_WINDOW: usize = 240;
+
+// Real frame times over the last STATS_WINDOW frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub average: f32,
+    pub min: f32,
+    pub max: f32,
+    // Mean of the slowest 1% of frames (at least one), i.e. the "1% low".
+    pub low_1_percent: f32,
+    // Frames per second measured over the last full second.
+    pub fps: f32,
+    // Frames the other fields were computed from.
+    pub frames: usize,
+}
+
+impl FrameStats {
+    fn from_frame_times(frame_times: &VecDeque<f32>, fps: f32) -> Self {
+        if frame_times.is_empty() {
+            return Self {
+                fps,
+                ..Self::default()
+            };
+        }
+        let mut sorted: Vec<f32> = frame_times.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+        let frames = sorted.len();
+        let slowest = frames.div_ceil(100);
+        Self {
+            average: sorted.iter().sum::<f32>() / frames as f32,
+            min: sorted[0],
+            max: sorted[frames - 1],
+            low_1_percent: sorted[frames - slowest..].iter().sum::<f32>() / slowest as f32,
+            fps,
+            frames,
+        }
+    }
+}
 
 pub struct Time {
     last_frame: Instant,
     accumulator: f32,
     fixed_dt: f32,
+    max_fixed_steps: u32,
+    time_scale: f32,
+    paused: bool,
     fps_timer: f32,
     fps_frames: u32,
+    fps: f32,
+    frame_times: VecDeque<f32>,
+    stats: FrameStats,
+    on_stats: Option<Box<dyn FnMut(FrameStats)>>,
+    title: String,
 }
 
 impl Time {
-    pub fn new(fixed_dt: f32) -> Self {
+    pub fn new(fixed_dt: f32, title: &str) -> Self {
+        let fixed_dt = validate_fixed_dt(fixed_dt);
         Self {
             last_frame: Instant::now(),
             accumulator: 0.0,
             fixed_dt,
+            max_fixed_steps: default_max_fixed_steps(fixed_dt),
+            time_scale: 1.0,
+            paused: false,
             fps_timer: 0.0,
             fps_frames: 0,
+            fps: 0.0,
+            frame_times: VecDeque::with_capacity(STATS_WINDOW),
+            stats: FrameStats::default(),
+            on_stats: None,
+            title: title.to_string(),
         }
     }
 
     pub fn advance(&mut self) -> f32 {
         let now = Instant::now();
-        let dt = (now - self.last_frame).as_secs_f32().min(MAX_DT);
+        let dt = (now - self.last_frame).as_secs_f32();
         self.last_frame = now;
+        self.accumulate(dt)
+    }
+
+    fn accumulate(&mut self, dt: f32) -> f32 {
+        let dt = dt.min(MAX_DT);
         self.accumulator += dt;
         dt
     }
 
+    // Never returns more than `max_fixed_steps`.
     pub fn consume_fixed_steps(&mut self) -> u32 {
         let mut steps = 0;
         while self.accumulator >= self.fixed_dt {
+            if steps == self.max_fixed_steps {
+                let dropped = self.accumulator - self.accumulator % self.fixed_dt;
+                self.accumulator -= dropped;
+                log::warn!(
+                    "Simulation fell behind, dropped {:.1} ms after {} fixed steps",
+                    dropped * 1000.0,
+                    steps
+                );
+                break;
+            }
             self.accumulator -= self.fixed_dt;
             steps += 1;
         }
         steps
     }
 
+    pub fn set_max_fixed_steps(&mut self, max_fixed_steps: u32) {
+        self.max_fixed_steps = max_fixed_steps.max(1);
+    }
+
     pub fn fixed_dt(&self) -> f32 {
         self.fixed_dt
     }
 
-    pub fn update_fps(&mut self, dt: f32, window: &Window, paused: bool) {
+    // Re-derives the step cap; returns the dt in use.
+    pub fn set_fixed_dt(&mut self, fixed_dt: f32) -> f32 {
+        self.fixed_dt = validate_fixed_dt(fixed_dt);
+        self.max_fixed_steps = default_max_fixed_steps(self.fixed_dt);
+        self.fixed_dt
+    }
+
+    pub fn max_fixed_steps(&self) -> u32 {
+        self.max_fixed_steps
+    }
+
+    // Simulation time advanced by one fixed step.
+    pub fn scaled_fixed_dt(&self) -> f32 {
+        if self.paused {
+            0.0
+        } else {
+            self.fixed_dt * self.time_scale
+        }
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    // Fraction of a fixed step left in the accumulator.
+    pub fn interpolation_alpha(&self) -> f32 {
+        (self.accumulator / self.fixed_dt).clamp(0.0, 1.0)
+    }
+
+    // Frames per second measured over the last full second.
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    // Called with the fresh stats once per second, e.g. for logging or an overlay.
+    pub fn set_on_stats(&mut self, callback: impl FnMut(FrameStats) + 'static) {
+        self.on_stats = Some(Box::new(callback));
+    }
+
+    // Returns true once per second, when `fps` and `stats` refresh.
+    pub fn record_frame(&mut self, dt: f32) -> bool {
+        if self.frame_times.len() == STATS_WINDOW {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(dt);
         self.fps_timer += dt;
         self.fps_frames += 1;
-        if self.fps_timer >= 1.0 {
-            let fps = self.fps_frames as f32 / self.fps_timer;
-            let paused_marker = if paused { " [paused]" } else { "" };
-            window.set_title(&format!("engine2d - {:.0} fps{}", fps, paused_marker));
-            self.fps_timer = 0.0;
-            self.fps_frames = 0;
+        if self.fps_timer < 1.0 {
+            return false;
+        }
+        self.fps = self.fps_frames as f32 / self.fps_timer;
+        self.fps_timer = 0.0;
+        self.fps_frames = 0;
+        self.stats = FrameStats::from_frame_times(&self.frame_times, self.fps);
+        if let Some(on_stats) = &mut self.on_stats {
+            on_stats(self.stats);
+        }
+        true
+    }
+
+    // `dt` is real time, so the reported FPS ignores the time scale.
+    pub fn update_fps(&mut self, dt: f32, window: &Window) {
+        if self.record_frame(dt) {
+            let paused_marker = if self.paused { " [paused]" } else { "" };
+            window.set_title(&format!(
+                "{} - {:.0} fps{}",
+                self.title, self.fps, paused_marker
+            ));
+        }
+    }
+}
+
+fn validate_fixed_dt(fixed_dt: f32) -> f32 {
+    if !(fixed_dt.is_finite() && fixed_dt > 0.0) {
+        log::warn!("Invalid fixed dt {}, using {}", fixed_dt, DEFAULT_FIXED_DT);
+        return DEFAULT_FIXED_DT;
+    }
+    let clamped = fixed_dt.clamp(MIN_FIXED_DT, MAX_FIXED_DT);
+    if clamped != fixed_dt {
+        log::warn!("Fixed dt {} out of range, using {}", fixed_dt, clamped);
+    }
+    clamped
+}
+
+fn default_max_fixed_steps(fixed_dt: f32) -> u32 {
+    ((MAX_CATCH_UP / fixed_dt).round() as u32).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_frame_time_is_clamped() {
+        let mut time = Time::new(1.0 / 60.0, "test");
+        assert_eq!(time.accumulate(3.0), MAX_DT);
+        assert_eq!(time.accumulator, MAX_DT);
+        assert_eq!(time.consume_fixed_steps(), 5);
+        assert!(time.accumulator < time.fixed_dt());
+    }
+
+    #[test]
+    fn stats_report_min_max_and_average_frame_times() {
+        let mut time = Time::new(1.0 / 60.0, "test");
+        for _ in 0..99 {
+            assert!(!time.record_frame(0.01));
         }
+        assert!(time.record_frame(0.1));
+        let stats = time.stats();
+        assert_eq!(stats.frames, 100);
+        assert_eq!(stats.min, 0.01);
+        assert_eq!(stats.max, 0.1);
+        assert_eq!(stats.low_1_percent, 0.1);
+        assert!((stats.average - 0.0109).abs() < 1e-5);
+        assert!((stats.fps - 100.0 / 1.09).abs() < 1e-2);
+    }
+
+    #[test]
+    fn thirty_hz_steps_every_thirtieth_of_a_second() {
+        let mut time = Time::new(1.0 / 30.0, "test");
+        assert_eq!(time.fixed_dt(), 1.0 / 30.0);
+        time.accumulate(0.07);
+        assert_eq!(time.consume_fixed_steps(), 2);
+        time.accumulate(1.0 / 60.0);
+        assert_eq!(time.consume_fixed_steps(), 0);
+        assert!((time.interpolation_alpha() - 0.6).abs() < 1e-3);
+        time.accumulate(1.0 / 60.0);
+        assert_eq!(time.consume_fixed_steps(), 1);
     }
 }
@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+#[derive(Default)]
+struct WatchState {
+    files: HashSet<PathBuf>,
+    last_change: Option<Instant>,
+}
+
+// Watches parent directories so rename-saves and new files are seen.
+pub struct AssetWatcher {
+    watcher: RecommendedWatcher,
+    state: Arc<Mutex<WatchState>>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl AssetWatcher {
+    // Returns None when the platform watcher cannot start.
+    pub fn new(paths: &[PathBuf]) -> Option<Self> {
+        let state = Arc::new(Mutex::new(WatchState::default()));
+        let handler_state = Arc::clone(&state);
+        let watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+            let event = match result {
+                Ok(event) => event,
+                Err(err) => {
+                    log::warn!("Asset watcher error: {}", err);
+                    return;
+                }
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            let Ok(mut state) = handler_state.lock() else {
+                return;
+            };
+            if event.paths.iter().any(|path| state.files.contains(path)) {
+                state.last_change = Some(Instant::now());
+            }
+        });
+
+        match watcher {
+            Ok(watcher) => {
+                let mut asset_watcher = Self {
+                    watcher,
+                    state,
+                    dirs: HashSet::new(),
+                };
+                asset_watcher.watch_paths(paths);
+                Some(asset_watcher)
+            }
+            Err(err) => {
+                log::warn!("Asset watcher unavailable, polling instead: {}", err);
+                None
+            }
+        }
+    }
+
+    // Replaces the watched set, e.g. after the atlas config switched textures.
+    pub fn watch_paths(&mut self, paths: &[PathBuf]) {
+        let mut files = HashSet::new();
+        let mut dirs = HashSet::new();
+        for path in paths {
+            let Some((dir, file)) = resolve(path) else {
+                log::warn!("Cannot watch {}: directory not found", path.display());
+                continue;
+            };
+            files.insert(dir.join(file));
+            dirs.insert(dir);
+        }
+
+        for dir in self.dirs.difference(&dirs) {
+            if let Err(err) = self.watcher.unwatch(dir) {
+                log::warn!("Failed to unwatch {}: {}", dir.display(), err);
+            }
+        }
+        for dir in dirs.difference(&self.dirs) {
+            if let Err(err) = self.watcher.watch(dir, RecursiveMode::NonRecursive) {
+                log::warn!("Failed to watch {}: {}", dir.display(), err);
+            }
+        }
+        self.dirs = dirs;
+
+        if let Ok(mut state) = self.state.lock() {
+            state.files = files;
+        }
+    }
+
+    // True once per burst of changes, after it has been quiet for DEBOUNCE.
+    pub fn take_changed(&mut self) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        match state.last_change {
+            Some(time) if time.elapsed() >= DEBOUNCE => {
+                state.last_change = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn resolve(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let file = PathBuf::from(path.file_name()?);
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let dir = parent.canonicalize().ok()?;
+    Some((dir, file))
+}